@@ -1,25 +1,38 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::fs::File;
 use std::io::Read;
 use std::rc::Rc;
-use std::sync::Mutex;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 extern crate rustyline;
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 
+use crate::lazy;
+use crate::numeric;
 use crate::printer::pr_seq;
 use crate::reader::read_str;
+use crate::types::ArgType as T;
 use crate::types::MalErr::ErrMalVal;
-use crate::types::MalVal::{Atom, Bool, Func, Hash, Int, List, MalFunc, Nil, Str, Sym, Vector};
-use crate::types::{MalArgs, MalRet, MalVal, _assoc, _dissoc, atom, error, func, hash_map};
-
-macro_rules! fn_t_int_int {
-    ($ret:ident, $fn:expr) => {{
-        |a: MalArgs| match (&a[0], &a[1]) {
-            (Int(a0), Int(a1)) => Ok($ret($fn(a0, a1))),
-            _ => error("expecting (int,int) args"),
-        }
+use crate::types::MalVal::{
+    Atom, BigInt, Bool, Float, Func, Hash, Int, Keyword, LazySeq, List, MalFunc, Nil, Ratio, Set,
+    Str, Sym, Vector,
+};
+use crate::types::{
+    _assoc, _conj_set, _dissoc, atom, error, ex_error, ex_key, func, gensym,
+    hash_map, hash_set, lazy_cons, type_error, Arity, Builtin, MalArgs, MalRet, MalSeq, MalVal,
+};
+
+macro_rules! fn_t_num {
+    ($fn:expr) => {{
+        |a: MalArgs| $fn(&a[0])
+    }};
+}
+
+macro_rules! fn_round {
+    ($name:expr, $method:ident) => {{
+        |a: MalArgs| numeric::round_with($name, f64::$method, |r| r.$method(), &a[0])
     }};
 }
 
@@ -39,7 +52,7 @@ macro_rules! fn_str {
     ($fn:expr) => {{
         |a: MalArgs| match &a[0] {
             Str(a0) => $fn(&a0),
-            _ => error("expecting (str) arg"),
+            _ => Err(type_error("expecting (str) arg", &a[0])),
         }
     }};
 }
@@ -47,21 +60,24 @@ macro_rules! fn_str {
 fn symbol(a: MalArgs) -> MalRet {
     match a[0] {
         Str(ref s) => Ok(Sym(s.to_string())),
-        _ => error("illegal symbol call"),
+        _ => Err(type_error("illegal symbol call", &a[0])),
     }
 }
 
-fn readline(a: MalArgs) -> MalRet {
-    lazy_static! {
-        static ref RL: Mutex<Editor<(), rustyline::history::DefaultHistory>>
-          = Mutex::new(Editor::<(), rustyline::history::DefaultHistory>::new().unwrap());
-    }
-    //let mut rl = Editor::<()>::new();
-
-    match a[0] {
+// The line editor is created on first use and owned by the builtin.
+fn readline() -> impl Fn(MalArgs) -> MalRet {
+    let rl: RefCell<Option<Editor<(), rustyline::history::DefaultHistory>>> = RefCell::new(None);
+    move |a| match a[0] {
         Str(ref p) => {
-            //match rl.readline(p) {
-            match RL.lock().unwrap().readline(p) {
+            let mut rl = rl.borrow_mut();
+            let ed = match *rl {
+                Some(ref mut ed) => ed,
+                None => match Editor::new() {
+                    Ok(ed) => rl.insert(ed),
+                    Err(e) => return error(&format!("{:?}", e)),
+                },
+            };
+            match ed.readline(p) {
                 Ok(mut line) => {
                     // Remove any trailing \n or \r\n
                     if line.ends_with('\n') {
@@ -76,7 +92,7 @@ fn readline(a: MalArgs) -> MalRet {
                 Err(e) => error(&format!("{:?}", e)),
             }
         }
-        _ => error("readline: prompt is not Str"),
+        _ => Err(type_error("readline: prompt is not Str", &a[0])),
     }
 }
 
@@ -101,96 +117,139 @@ fn time_ms(_a: MalArgs) -> MalRet {
 fn get(a: MalArgs) -> MalRet {
     match (a[0].clone(), a[1].clone()) {
         (Nil, _) => Ok(Nil),
-        (Hash(ref hm, _), Str(ref s)) => match hm.get(s) {
+        (Hash(ref hm, _), ref k) => match hm.get(k) {
             Some(mv) => Ok(mv.clone()),
             None => Ok(Nil),
         },
-        _ => error("illegal get args"),
+        (Set(ref s, _), ref k) if s.contains(k) => Ok(k.clone()),
+        (Set(_, _), _) => Ok(Nil),
+        _ => Err(type_error("illegal get args", &a[0])),
     }
 }
 
 fn assoc(a: MalArgs) -> MalRet {
     match a[0] {
         Hash(ref hm, _) => _assoc((**hm).clone(), a[1..].to_vec()),
-        _ => error("assoc on non-Hash Map"),
+        _ => Err(type_error("assoc on non-Hash Map", &a[0])),
     }
 }
 
 fn dissoc(a: MalArgs) -> MalRet {
     match a[0] {
         Hash(ref hm, _) => _dissoc((**hm).clone(), a[1..].to_vec()),
-        _ => error("dissoc on non-Hash Map"),
+        _ => Err(type_error("dissoc on non-Hash Map", &a[0])),
+    }
+}
+
+fn set(a: MalArgs) -> MalRet {
+    match a[0] {
+        List(ref v, _) | Vector(ref v, _) => hash_set(v.iter().cloned().collect()),
+        Set(_, _) => Ok(a[0].clone()),
+        Nil => hash_set(vec![]),
+        LazySeq(_) => hash_set(a[0].realize_all()?.into_iter().collect()),
+        _ => Err(type_error("set: called with non-seq", &a[0])),
+    }
+}
+
+fn disj(a: MalArgs) -> MalRet {
+    match a[0] {
+        Set(ref s, _) => {
+            let mut new_s = (**s).clone();
+            for k in a[1..].iter() {
+                new_s.remove(k);
+            }
+            Ok(Set(Rc::new(new_s), Rc::new(Nil)))
+        }
+        _ => Err(type_error("disj on non-Set", &a[0])),
     }
 }
 
 fn contains_q(a: MalArgs) -> MalRet {
     match (a[0].clone(), a[1].clone()) {
-        (Hash(ref hm, _), Str(ref s)) => Ok(Bool(hm.contains_key(s))),
-        _ => error("illegal get args"),
+        (Hash(ref hm, _), ref k) => Ok(Bool(hm.contains_key(k))),
+        (Set(ref s, _), ref k) => Ok(Bool(s.contains(k))),
+        _ => Err(type_error("illegal get args", &a[0])),
     }
 }
 
 fn keys(a: MalArgs) -> MalRet {
     match a[0] {
-        Hash(ref hm, _) => Ok(list!(hm.keys().map(|k| { Str(k.to_string()) }).collect())),
-        _ => error("keys requires Hash Map"),
+        Hash(ref hm, _) => Ok(list!(hm.keys().cloned().collect::<MalSeq>())),
+        _ => Err(type_error("keys requires Hash Map", &a[0])),
     }
 }
 
 fn vals(a: MalArgs) -> MalRet {
     match a[0] {
-        Hash(ref hm, _) => Ok(list!(hm.values().cloned().collect())),
-        _ => error("keys requires Hash Map"),
+        Hash(ref hm, _) => Ok(list!(hm.values().cloned().collect::<MalSeq>())),
+        _ => Err(type_error("vals requires Hash Map", &a[0])),
     }
 }
 
 fn vec(a: MalArgs) -> MalRet {
     match a[0] {
-        List(ref v, _) | Vector(ref v, _) => Ok(vector!(v.to_vec())),
-        _ => error("non-seq passed to vec"),
+        List(ref v, _) | Vector(ref v, _) => Ok(vector!((**v).clone())),
+        _ => Ok(vector!(a[0].realize_all()?)),
     }
 }
 
 fn cons(a: MalArgs) -> MalRet {
     match a[1].clone() {
         List(v, _) | Vector(v, _) => {
-            let mut new_v = vec![a[0].clone()];
-            new_v.extend_from_slice(&v);
-            Ok(list!(new_v.to_vec()))
+            let mut new_v = (*v).clone();
+            new_v.push_front(a[0].clone());
+            Ok(list!(new_v))
+        }
+        // consing onto a lazy seq must not realize it
+        LazySeq(_) => Ok(lazy_cons(a[0].clone(), a[1].clone())),
+        _ => {
+            let mut new_v = a[1].realize_all()?;
+            new_v.push_front(a[0].clone());
+            Ok(list!(new_v))
         }
-        _ => error("cons expects seq as second arg"),
     }
 }
 
 fn concat(a: MalArgs) -> MalRet {
-    let mut new_v = vec![];
+    if a.iter().any(|s| matches!(s, LazySeq(_))) {
+        return lazy::concat(a);
+    }
+    let mut new_v = MalSeq::new();
     for seq in a.iter() {
         match seq {
-            List(v, _) | Vector(v, _) => new_v.extend_from_slice(v),
-            _ => return error("non-seq passed to concat"),
+            List(v, _) | Vector(v, _) => new_v.append((**v).clone()),
+            _ => new_v.append(seq.realize_all()?),
         }
     }
-    Ok(list!(new_v.to_vec()))
+    Ok(list!(new_v))
 }
 
 fn nth(a: MalArgs) -> MalRet {
+    let out_of_range = || {
+        let data = vec![("index", a[1].clone())];
+        Err(ex_error("out-of-range", "nth: index out of range", data))
+    };
     match (a[0].clone(), a[1].clone()) {
         (List(seq, _), Int(idx)) | (Vector(seq, _), Int(idx)) => {
             if seq.len() <= idx as usize {
-                return error("nth: index out of range");
+                return out_of_range();
             }
             Ok(seq[idx as usize].clone())
         }
-        _ => error("invalid args to nth"),
+        (_, Int(idx)) if idx >= 0 => match a[0].seq_iter().nth(idx as usize) {
+            Some(x) => x,
+            None => out_of_range(),
+        },
+        (_, Int(_)) => out_of_range(),
+        _ => Err(type_error("invalid args to nth", &a[1])),
     }
 }
 
 fn first(a: MalArgs) -> MalRet {
     match a[0].clone() {
-        List(ref seq, _) | Vector(ref seq, _) if seq.len() == 0 => Ok(Nil),
+        List(ref seq, _) | Vector(ref seq, _) if seq.is_empty() => Ok(Nil),
         List(ref seq, _) | Vector(ref seq, _) => Ok(seq[0].clone()),
-        Nil => Ok(Nil),
-        _ => error("invalid args to first"),
+        _ => Ok(a[0].uncons()?.map_or(Nil, |(x, _)| x)),
     }
 }
 
@@ -198,13 +257,12 @@ fn rest(a: MalArgs) -> MalRet {
     match a[0].clone() {
         List(ref seq, _) | Vector(ref seq, _) => {
             if seq.len() > 1 {
-                Ok(list!(seq[1..].to_vec()))
+                Ok(list!(seq.skip(1)))
             } else {
                 Ok(list![])
             }
         }
-        Nil => Ok(list![]),
-        _ => error("invalid args to first"),
+        _ => Ok(a[0].uncons()?.map_or(list![], |(_, rest)| rest)),
     }
 }
 
@@ -213,85 +271,187 @@ fn apply(a: MalArgs) -> MalRet {
         List(ref v, _) | Vector(ref v, _) => {
             let f = &a[0];
             let mut fargs = a[1..a.len() - 1].to_vec();
-            fargs.extend_from_slice(v);
+            fargs.extend(v.iter().cloned());
+            f.apply(fargs)
+        }
+        _ => {
+            let f = &a[0];
+            let mut fargs = a[1..a.len() - 1].to_vec();
+            fargs.extend(a[a.len() - 1].realize_all()?);
             f.apply(fargs)
         }
-        _ => error("apply called with non-seq"),
     }
 }
 
 fn map(a: MalArgs) -> MalRet {
-    match a[1] {
-        List(ref v, _) | Vector(ref v, _) => {
-            let mut res = vec![];
-            for mv in v.iter() {
-                res.push(a[0].apply(vec![mv.clone()])?)
-            }
-            Ok(list!(res))
-        }
-        _ => error("map called with non-seq"),
+    if let LazySeq(_) = a[1] {
+        return lazy::map(a);
+    }
+    let mut res = MalSeq::new();
+    for mv in a[1].realize_all()? {
+        res.push_back(a[0].apply(vec![mv])?)
     }
+    Ok(list!(res))
 }
 
 fn conj(a: MalArgs) -> MalRet {
     match a[0] {
         List(ref v, _) => {
-            let sl = a[1..]
-                .iter()
-                .rev()
-                .cloned()
-                .collect::<Vec<MalVal>>();
-            Ok(list!([&sl[..], v].concat()))
+            let mut new_v = (**v).clone();
+            for mv in a[1..].iter() {
+                new_v.push_front(mv.clone());
+            }
+            Ok(list!(new_v))
         }
-        Vector(ref v, _) => Ok(vector!([v, &a[1..]].concat())),
-        _ => error("conj: called with non-seq"),
+        Vector(ref v, _) => {
+            let mut new_v = (**v).clone();
+            new_v.extend(a[1..].iter().cloned());
+            Ok(vector!(new_v))
+        }
+        Set(ref s, _) => _conj_set((**s).clone(), a[1..].to_vec()),
+        LazySeq(_) => Ok(a[1..]
+            .iter()
+            .fold(a[0].clone(), |s, mv| lazy_cons(mv.clone(), s))),
+        // onto nil, as onto an empty list
+        Nil => Ok(list!(a[1..].iter().rev().cloned().collect::<MalSeq>())),
+        _ => Err(type_error("conj: called with non-seq", &a[0])),
     }
 }
 
 fn seq(a: MalArgs) -> MalRet {
     match a[0] {
-        List(ref v, _) | Vector(ref v, _) if v.len() == 0 => Ok(Nil),
-        List(ref v, _) | Vector(ref v, _) => Ok(list!(v.to_vec())),
+        List(ref v, _) | Vector(ref v, _) if v.is_empty() => Ok(Nil),
+        List(ref v, _) | Vector(ref v, _) => Ok(list!((**v).clone())),
+        Set(ref s, _) if s.is_empty() => Ok(Nil),
+        Set(ref s, _) => Ok(list!(s.iter().cloned().collect::<MalSeq>())),
         Str(ref s) if s.is_empty() => Ok(Nil),
-        Str(ref s) if !a[0].keyword_q() => {
-            Ok(list!(s.chars().map(|c| { Str(c.to_string()) }).collect()))
-        }
+        Str(ref s) => Ok(list!(s
+            .chars()
+            .map(|c| { Str(c.to_string()) })
+            .collect::<MalSeq>())),
         Nil => Ok(Nil),
-        _ => error("seq: called with non-seq"),
+        LazySeq(_) => match a[0].uncons()? {
+            Some(_) => Ok(a[0].clone()),
+            None => Ok(Nil),
+        },
+        _ => Err(type_error("seq: called with non-seq", &a[0])),
+    }
+}
+
+// (ex-info msg data) and (ex-info msg data cause): the data, with the
+// message and cause added under reserved keys (see types::ex_key).
+fn ex_info(a: MalArgs) -> MalRet {
+    match (&a[0], &a[1]) {
+        (Str(_), Hash(hm, _)) => {
+            let mut hm = (**hm).clone();
+            hm.insert(ex_key("message"), a[0].clone());
+            if let Some(cause) = a.get(2) {
+                hm.insert(ex_key("cause"), cause.clone());
+            }
+            Ok(Hash(Rc::new(hm), Rc::new(Nil)))
+        }
+        (Str(_), v) | (v, _) => Err(type_error("ex-info: expecting a message and a hash-map", v)),
     }
 }
 
+fn ex_data(a: MalArgs) -> MalRet {
+    Ok(a[0].ex_data().unwrap_or(Nil))
+}
+
+// The message of an ex-info, or a thrown string itself.
+fn ex_message(a: MalArgs) -> MalRet {
+    match a[0] {
+        Str(_) => Ok(a[0].clone()),
+        _ => Ok(a[0].ex_message().map_or(Nil, Str)),
+    }
+}
+
+fn ex_cause(a: MalArgs) -> MalRet {
+    match a[0] {
+        Hash(ref hm, _) if a[0].ex_message().is_some() => {
+            Ok(hm.get(&ex_key("cause")).cloned().unwrap_or(Nil))
+        }
+        _ => Ok(Nil),
+    }
+}
+
+// Each builtin with its signature: the number of arguments it takes and
+// their types, which MalVal::apply checks before calling it (see
+// Builtin::types for how the types line up with the arguments).
 pub fn ns() -> Vec<(&'static str, MalVal)> {
+    let one = || Arity::exactly(1);
+    let two = || Arity::exactly(2);
+    let any = || Arity::at_least(0);
     vec![
-        ("=", func(|a| Ok(Bool(a[0] == a[1])))),
-        ("throw", func(|a| Err(ErrMalVal(a[0].clone())))),
-        ("nil?", func(fn_is_type!(Nil))),
-        ("true?", func(fn_is_type!(Bool(true)))),
-        ("false?", func(fn_is_type!(Bool(false)))),
-        ("symbol", func(symbol)),
-        ("symbol?", func(fn_is_type!(Sym(_)))),
+        ("=", two(), vec![], func(|a| Ok(Bool(a[0] == a[1])))),
+        (
+            "throw",
+            one(),
+            vec![],
+            func(|a| Err(ErrMalVal(a[0].clone()))),
+        ),
         (
-            "string?",
-            func(fn_is_type!(Str(ref s) if !s.starts_with('\u{29e}'))),
+            "ex-info",
+            Arity::between(2, 3),
+            vec![T::Str, T::Map, T::Any],
+            func(ex_info),
         ),
-        ("keyword", func(|a| a[0].keyword())),
+        ("ex-data", one(), vec![], func(ex_data)),
+        ("ex-message", one(), vec![], func(ex_message)),
+        ("ex-cause", one(), vec![], func(ex_cause)),
+        ("nil?", one(), vec![], func(fn_is_type!(Nil))),
+        ("true?", one(), vec![], func(fn_is_type!(Bool(true)))),
+        ("false?", one(), vec![], func(fn_is_type!(Bool(false)))),
+        ("symbol", one(), vec![T::Str], func(symbol)),
+        ("symbol?", one(), vec![], func(fn_is_type!(Sym(_)))),
         (
-            "keyword?",
-            func(fn_is_type!(Str(ref s) if s.starts_with('\u{29e}'))),
+            "gensym",
+            Arity::between(0, 1),
+            vec![T::Str],
+            func(|a| match a.first() {
+                Some(Str(prefix)) => Ok(gensym(prefix)),
+                _ => Ok(gensym("G__")),
+            }),
         ),
-        ("number?", func(fn_is_type!(Int(_)))),
+        ("string?", one(), vec![], func(fn_is_type!(Str(_)))),
+        ("keyword", one(), vec![], func(|a| a[0].keyword())),
+        ("keyword?", one(), vec![], func(fn_is_type!(Keyword(_)))),
+        (
+            "number?",
+            one(),
+            vec![],
+            func(fn_is_type!(Int(_), BigInt(_), Ratio(_), Float(_))),
+        ),
+        ("ratio?", one(), vec![], func(fn_is_type!(Ratio(_)))),
+        ("float?", one(), vec![], func(fn_is_type!(Float(_)))),
         (
             "fn?",
+            one(),
+            vec![],
             func(fn_is_type!(MalFunc{is_macro,..} if !is_macro,Func(_,_))),
         ),
         (
             "macro?",
+            one(),
+            vec![],
             func(fn_is_type!(MalFunc{is_macro,..} if is_macro)),
         ),
-        ("pr-str", func(|a| Ok(Str(pr_seq(&a, true, "", "", " "))))),
-        ("str", func(|a| Ok(Str(pr_seq(&a, false, "", "", ""))))),
+        (
+            "pr-str",
+            any(),
+            vec![],
+            func(|a| Ok(Str(pr_seq(&a, true, "", "", " ")))),
+        ),
+        (
+            "str",
+            any(),
+            vec![],
+            func(|a| Ok(Str(pr_seq(&a, false, "", "", "")))),
+        ),
         (
             "prn",
+            any(),
+            vec![],
             func(|a| {
                 println!("{}", pr_seq(&a, true, "", "", " "));
                 Ok(Nil)
@@ -299,54 +459,189 @@ pub fn ns() -> Vec<(&'static str, MalVal)> {
         ),
         (
             "println",
+            any(),
+            vec![],
             func(|a| {
                 println!("{}", pr_seq(&a, false, "", "", " "));
                 Ok(Nil)
             }),
         ),
-        ("read-string", func(fn_str!(read_str))),
-        ("readline", func(readline)),
-        ("slurp", func(fn_str!(slurp))),
-        ("<", func(fn_t_int_int!(Bool, |i, j| { i < j }))),
-        ("<=", func(fn_t_int_int!(Bool, |i, j| { i <= j }))),
-        (">", func(fn_t_int_int!(Bool, |i, j| { i > j }))),
-        (">=", func(fn_t_int_int!(Bool, |i, j| { i >= j }))),
-        ("+", func(fn_t_int_int!(Int, |i, j| { i + j }))),
-        ("-", func(fn_t_int_int!(Int, |i, j| { i - j }))),
-        ("*", func(fn_t_int_int!(Int, |i, j| { i * j }))),
-        ("/", func(fn_t_int_int!(Int, |i, j| { i / j }))),
-        ("time-ms", func(time_ms)),
-        ("sequential?", func(fn_is_type!(List(_, _), Vector(_, _)))),
-        ("list", func(|a| Ok(list!(a.to_vec())))),
-        ("list?", func(fn_is_type!(List(_, _)))),
-        ("vector", func(|a| Ok(vector!(a.to_vec())))),
-        ("vector?", func(fn_is_type!(Vector(_, _)))),
-        ("hash-map", func(hash_map)),
-        ("map?", func(fn_is_type!(Hash(_, _)))),
-        ("assoc", func(assoc)),
-        ("dissoc", func(dissoc)),
-        ("get", func(get)),
-        ("contains?", func(contains_q)),
-        ("keys", func(keys)),
-        ("vals", func(vals)),
-        ("vec", func(vec)),
-        ("cons", func(cons)),
-        ("concat", func(concat)),
-        ("empty?", func(|a| a[0].empty_q())),
-        ("nth", func(nth)),
-        ("first", func(first)),
-        ("rest", func(rest)),
-        ("count", func(|a| a[0].count())),
-        ("apply", func(apply)),
-        ("map", func(map)),
-        ("conj", func(conj)),
-        ("seq", func(seq)),
-        ("meta", func(|a| a[0].get_meta())),
-        ("with-meta", func(|a| a[0].clone().with_meta(&a[1]))),
-        ("atom", func(|a| Ok(atom(&a[0])))),
-        ("atom?", func(fn_is_type!(Atom(_)))),
-        ("deref", func(|a| a[0].deref())),
-        ("reset!", func(|a| a[0].reset_bang(&a[1]))),
-        ("swap!", func(|a| a[0].swap_bang(&a[1..].to_vec()))),
+        ("read-string", one(), vec![T::Str], func(fn_str!(read_str))),
+        ("readline", one(), vec![T::Str], func(readline())),
+        ("slurp", one(), vec![T::Str], func(fn_str!(slurp))),
+        (
+            "<",
+            Arity::at_least(1),
+            vec![T::Num],
+            func(|a| numeric::chain("<", Ordering::is_lt, a)),
+        ),
+        (
+            "<=",
+            Arity::at_least(1),
+            vec![T::Num],
+            func(|a| numeric::chain("<=", Ordering::is_le, a)),
+        ),
+        (
+            ">",
+            Arity::at_least(1),
+            vec![T::Num],
+            func(|a| numeric::chain(">", Ordering::is_gt, a)),
+        ),
+        (
+            ">=",
+            Arity::at_least(1),
+            vec![T::Num],
+            func(|a| numeric::chain(">=", Ordering::is_ge, a)),
+        ),
+        (
+            "+",
+            any(),
+            vec![T::Num],
+            func(|a| numeric::fold(0, numeric::add, a)),
+        ),
+        (
+            "-",
+            Arity::at_least(1),
+            vec![T::Num],
+            func(|a| numeric::fold(0, numeric::sub, a)),
+        ),
+        (
+            "*",
+            any(),
+            vec![T::Num],
+            func(|a| numeric::fold(1, numeric::mul, a)),
+        ),
+        ("/", Arity::at_least(1), vec![T::Num], func(numeric::divide)),
+        ("int", one(), vec![T::Num], func(fn_t_num!(numeric::int))),
+        (
+            "double",
+            one(),
+            vec![T::Num],
+            func(fn_t_num!(numeric::double)),
+        ),
+        (
+            "floor",
+            one(),
+            vec![T::Num],
+            func(fn_round!("floor", floor)),
+        ),
+        ("ceil", one(), vec![T::Num], func(fn_round!("ceil", ceil))),
+        (
+            "round",
+            one(),
+            vec![T::Num],
+            func(fn_round!("round", round)),
+        ),
+        ("sqrt", one(), vec![T::Num], func(fn_t_num!(numeric::sqrt))),
+        (
+            "numerator",
+            one(),
+            vec![T::Num],
+            func(fn_t_num!(numeric::numerator)),
+        ),
+        (
+            "denominator",
+            one(),
+            vec![T::Num],
+            func(fn_t_num!(numeric::denominator)),
+        ),
+        ("time-ms", Arity::exactly(0), vec![], func(time_ms)),
+        (
+            "sequential?",
+            one(),
+            vec![],
+            func(fn_is_type!(List(_, _), Vector(_, _), LazySeq(_))),
+        ),
+        ("list", any(), vec![], func(|a| Ok(list!(a.to_vec())))),
+        ("list?", one(), vec![], func(fn_is_type!(List(_, _)))),
+        ("vector", any(), vec![], func(|a| Ok(vector!(a.to_vec())))),
+        ("vector?", one(), vec![], func(fn_is_type!(Vector(_, _)))),
+        ("hash-map", any(), vec![], func(hash_map)),
+        ("map?", one(), vec![], func(fn_is_type!(Hash(_, _)))),
+        ("hash-set", any(), vec![], func(hash_set)),
+        ("set", one(), vec![T::Seq], func(set)),
+        ("set?", one(), vec![], func(fn_is_type!(Set(_, _)))),
+        ("disj", Arity::at_least(1), vec![T::Set, T::Any], func(disj)),
+        (
+            "assoc",
+            Arity::at_least(1),
+            vec![T::Map, T::Any],
+            func(assoc),
+        ),
+        (
+            "dissoc",
+            Arity::at_least(1),
+            vec![T::Map, T::Any],
+            func(dissoc),
+        ),
+        ("get", two(), vec![], func(get)),
+        ("contains?", two(), vec![], func(contains_q)),
+        ("keys", one(), vec![T::Map], func(keys)),
+        ("vals", one(), vec![T::Map], func(vals)),
+        ("vec", one(), vec![T::Seq], func(vec)),
+        ("cons", two(), vec![T::Any, T::Seq], func(cons)),
+        ("concat", any(), vec![T::Seq], func(concat)),
+        ("empty?", one(), vec![], func(|a| a[0].empty_q())),
+        ("nth", two(), vec![T::Seq, T::Int], func(nth)),
+        ("first", one(), vec![T::Seq], func(first)),
+        ("rest", one(), vec![T::Seq], func(rest)),
+        ("count", one(), vec![], func(|a| a[0].count())),
+        (
+            "apply",
+            Arity::at_least(2),
+            vec![T::Fn, T::Any],
+            func(apply),
+        ),
+        ("map", two(), vec![T::Fn, T::Seq], func(map)),
+        ("conj", Arity::at_least(1), vec![T::Seq, T::Any], func(conj)),
+        ("seq", one(), vec![], func(seq)),
+        ("range", Arity::between(0, 3), vec![], func(lazy::range)),
+        ("iterate", two(), vec![T::Fn, T::Any], func(lazy::iterate)),
+        ("repeat", Arity::between(1, 2), vec![], func(lazy::repeat)),
+        ("cycle", one(), vec![T::Seq], func(lazy::cycle)),
+        ("take", two(), vec![T::Int, T::Seq], func(lazy::take)),
+        ("drop", two(), vec![T::Int, T::Seq], func(lazy::drop)),
+        (
+            "take-while",
+            two(),
+            vec![T::Fn, T::Seq],
+            func(lazy::take_while),
+        ),
+        ("meta", one(), vec![], func(|a| a[0].get_meta())),
+        (
+            "with-meta",
+            two(),
+            vec![],
+            func(|a| a[0].clone().with_meta(&a[1])),
+        ),
+        ("atom", one(), vec![], func(|a| Ok(atom(&a[0])))),
+        ("atom?", one(), vec![], func(fn_is_type!(Atom(_)))),
+        ("deref", one(), vec![T::Atom], func(|a| a[0].deref())),
+        (
+            "reset!",
+            two(),
+            vec![T::Atom, T::Any],
+            func(|a| a[0].reset_bang(&a[1])),
+        ),
+        (
+            "swap!",
+            Arity::at_least(2),
+            vec![T::Atom, T::Fn, T::Any],
+            func(|a| a[0].swap_bang(&a[1..].to_vec())),
+        ),
     ]
+    .into_iter()
+    .map(|(name, arity, types, f)| match f {
+        Func(b, meta) => {
+            let b = Builtin {
+                name: name.to_string(),
+                arity: Some(arity),
+                types,
+                ..(*b).clone()
+            };
+            (name, Func(Rc::new(b), meta))
+        }
+        _ => (name, f),
+    })
+    .collect()
 }
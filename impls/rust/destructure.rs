@@ -0,0 +1,145 @@
+use crate::env::{env_set, Env};
+use crate::eval::eval;
+use crate::types::MalVal::{Hash, Keyword, List, Nil, Str, Sym, Vector};
+use crate::types::{gensym, keyword, syntax_error, type_error, MalErr, MalMap, MalSeq, MalVal};
+
+// Destructuring binding forms, as in Clojure:
+//
+//   [a b & rest :as all]          a seq, by position
+//   {:keys [x y] :or {y 0} :as m} a map, by keyword (or :strs, :syms)
+//   {a :a [b c] :bc}              a map, by explicit key
+//
+// Patterns nest. Missing elements and keys bind to nil unless an :or
+// default is given; the defaults are evaluated in the environment being
+// bound, so they can refer to bindings made before them.
+
+pub fn bind(env: &Env, pattern: &MalVal, value: MalVal) -> Result<(), MalErr> {
+    match pattern {
+        Sym(_) => env_set(env, pattern, value).map(|_| ()),
+        List(ps, _) | Vector(ps, _) => bind_seq(env, ps, value),
+        Hash(hm, _) => bind_map(env, hm, value),
+        _ => Err(syntax_error(&format!(
+            "cannot bind to {}",
+            pattern.pr_str(true)
+        ))),
+    }
+}
+
+fn bind_seq(env: &Env, ps: &MalSeq, value: MalVal) -> Result<(), MalErr> {
+    let mut rest = value.clone();
+    let mut i = 0;
+    while i < ps.len() {
+        match &ps[i] {
+            Sym(s) if s == "&" => {
+                let target = follower(ps, i, "&")?;
+                let r = match rest {
+                    Nil => list![],
+                    _ => rest.clone(),
+                };
+                bind(env, target, r)?;
+                rest = Nil;
+                i += 2;
+            }
+            Keyword(k) if &**k == "as" => {
+                bind(env, follower(ps, i, ":as")?, value.clone())?;
+                i += 2;
+            }
+            p => {
+                let (x, r) = rest.uncons()?.unwrap_or((Nil, Nil));
+                bind(env, p, x)?;
+                rest = r;
+                i += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn follower<'a>(ps: &'a MalSeq, i: usize, what: &str) -> Result<&'a MalVal, MalErr> {
+    ps.get(i + 1)
+        .ok_or_else(|| syntax_error(&format!("{} must be followed by a binding", what)))
+}
+
+fn bind_map(env: &Env, hm: &MalMap, value: MalVal) -> Result<(), MalErr> {
+    let m = match value {
+        Hash(ref m, _) => Some(m.clone()),
+        Nil => None,
+        _ => {
+            let msg = format!("cannot destructure {} as a map", value.pr_str(true));
+            return Err(type_error(&msg, &value));
+        }
+    };
+    let defaults = hm.get(&keyword("or"));
+    let lookup = |key: &MalVal, target: &MalVal| -> Result<MalVal, MalErr> {
+        if let Some(v) = m.as_ref().and_then(|m| m.get(key)) {
+            return Ok(v.clone());
+        }
+        match defaults {
+            Some(Hash(ds, _)) => match ds.get(target) {
+                Some(d) => eval(d, env),
+                None => Ok(Nil),
+            },
+            _ => Ok(Nil),
+        }
+    };
+    for (k, v) in hm.iter() {
+        match k {
+            Keyword(kw) if &**kw == "keys" || &**kw == "strs" || &**kw == "syms" => {
+                let names = match v {
+                    List(names, _) | Vector(names, _) => names,
+                    _ => {
+                        return Err(syntax_error(&format!(
+                            ":{} must be followed by a vector",
+                            kw
+                        )))
+                    }
+                };
+                for name in names.iter() {
+                    let s = match name {
+                        Sym(s) => s,
+                        _ => return Err(syntax_error(&format!(":{} takes symbols", kw))),
+                    };
+                    let key = match &**kw {
+                        "keys" => keyword(s),
+                        "strs" => Str(s.clone()),
+                        _ => Sym(s.clone()),
+                    };
+                    env_set(env, name, lookup(&key, name)?)?;
+                }
+            }
+            Keyword(kw) if &**kw == "as" => {
+                env_set(env, v, value.clone())?;
+            }
+            Keyword(kw) if &**kw == "or" => (),
+            _ => bind(env, k, lookup(v, k)?)?,
+        }
+    }
+    Ok(())
+}
+
+// Replace each destructuring pattern in a fn* parameter list with a
+// fresh plain parameter, and bind the patterns to those in a let* around the
+// body, so env_bind only ever sees symbols.
+pub fn fn_params(params: &MalVal, body: &MalVal) -> (MalVal, MalVal) {
+    let ps = match params {
+        List(ps, _) | Vector(ps, _) if ps.iter().any(|p| !matches!(p, Sym(_))) => ps,
+        _ => return (params.clone(), body.clone()),
+    };
+    let mut new_ps = vec![];
+    let mut binds = vec![];
+    for p in ps.iter() {
+        match p {
+            Sym(_) => new_ps.push(p.clone()),
+            _ => {
+                let s = gensym("p__");
+                new_ps.push(s.clone());
+                binds.push(p.clone());
+                binds.push(s);
+            }
+        }
+    }
+    (
+        vector!(new_ps),
+        list![Sym("let*".to_string()), vector!(binds), body.clone()],
+    )
+}
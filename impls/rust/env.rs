@@ -3,9 +3,8 @@ use std::rc::Rc;
 //use std::collections::HashMap;
 use fnv::FnvHashMap;
 
-use crate::types::MalErr::ErrString;
-use crate::types::MalVal::{List, Nil, Sym, Vector};
-use crate::types::{error, MalErr, MalRet, MalVal};
+use crate::types::MalVal::{List, Sym, Vector};
+use crate::types::{arg_count_error, param_count, syntax_error, MalErr, MalRet, MalVal};
 
 pub struct EnvStruct {
     data: RefCell<FnvHashMap<String, MalVal>>,
@@ -24,25 +23,40 @@ pub fn env_new(outer: Option<Env>) -> Env {
     })
 }
 
+// Bind the parameters of a function called by name (None if it is
+// anonymous) to its arguments. The number of arguments is checked
+// against the parameters first, so a bad call is a catchable error.
 // TODO: mbinds and exprs as & types
-pub fn env_bind(outer: Option<Env>, mbinds: &MalVal, exprs: Vec<MalVal>) -> Result<Env, MalErr> {
+pub fn env_bind(
+    outer: Option<Env>,
+    mbinds: &MalVal,
+    exprs: Vec<MalVal>,
+    name: Option<&str>,
+) -> Result<Env, MalErr> {
     let env = env_new(outer);
     match mbinds {
         List(binds, _) | Vector(binds, _) => {
-            for (i, b) in binds.iter().enumerate() {
-                match b {
-                    Sym(s) if s == "&" => {
-                        env_set(&env, &binds[i + 1], list!(exprs[i..].to_vec()))?;
-                        break;
-                    }
-                    _ => {
-                        env_set(&env, b, exprs[i].clone())?;
-                    }
-                }
+            let (fixed, variadic) = param_count(mbinds);
+            if variadic && binds.len() != fixed + 2 {
+                return Err(syntax_error("& must be followed by exactly one parameter"));
+            }
+            if exprs.len() < fixed || (!variadic && exprs.len() > fixed) {
+                let expected = match variadic {
+                    true => format!("at least {}", fixed),
+                    false => fixed.to_string(),
+                };
+                return Err(arg_count_error(name, &expected, exprs.len()));
+            }
+            let mut exprs = exprs.into_iter();
+            for b in binds.iter().take(fixed) {
+                env_set(&env, b, exprs.next().unwrap())?;
+            }
+            if variadic {
+                env_set(&env, &binds[fixed + 1], list!(exprs.collect::<Vec<_>>()))?;
             }
             Ok(env)
         }
-        _ => Err(ErrString("env_bind binds not List/Vector".to_string())),
+        _ => Err(syntax_error("env_bind binds not List/Vector")),
     }
 }
 
@@ -59,6 +73,28 @@ pub fn env_get(env: &Env, key: &str) -> Option<MalVal> {
     }
 }
 
+// The environment, env or one of its outer ones, that binds key. Only
+// stepA looks up bindings this way, for ex-stack.
+#[allow(dead_code)]
+pub fn env_find(env: &Env, key: &str) -> Option<Env> {
+    let mut mut_env = env;
+    loop {
+        if mut_env.data.borrow().contains_key(key) {
+            return Some(mut_env.clone());
+        } else if let Some(outer) = &mut_env.outer {
+            mut_env = outer;
+        } else {
+            return None;
+        }
+    }
+}
+
+// The value key is bound to in env itself, not its outer environments.
+#[allow(dead_code)]
+pub fn env_get_own(env: &Env, key: &str) -> Option<MalVal> {
+    env.data.borrow().get(key).cloned()
+}
+
 pub fn env_find_repl(env: &Env) -> Env {
     let mut mut_env = env;
     while let Some(outer) = &mut_env.outer {
@@ -73,7 +109,7 @@ pub fn env_set(env: &Env, key: &MalVal, val: MalVal) -> MalRet {
             env_sets(env, s, val.clone());
             Ok(val)
         }
-        _ => error("Env.set called with non-Str"),
+        _ => Err(syntax_error("Env.set called with non-Str")),
     }
 }
 
@@ -0,0 +1,936 @@
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use fnv::FnvHashMap;
+use itertools::Itertools;
+
+use crate::destructure::{bind, fn_params};
+use crate::env::{
+    env_bind, env_find, env_find_repl, env_get, env_get_own, env_new, env_set, env_sets, Env,
+};
+use crate::types::MalVal::{Bool, Func, Hash, List, MalFunc, Nil, Set, Sym, Vector};
+use crate::types::{
+    describe_fn, ex_error, fn_clause, hash_map, hash_set, lazy_seq, param_count, syntax_error,
+    type_error, MalArgs, MalErr, MalMap, MalRet, MalSeq, MalSet, MalVal,
+};
+
+fn qq_iter(elts: &MalSeq) -> MalVal {
+    let mut acc = list![];
+    for elt in elts.iter().rev() {
+        if let List(v, _) = elt {
+            if v.len() == 2 {
+                if let Sym(ref s) = v[0] {
+                    if s == "splice-unquote" {
+                        acc = list![Sym("concat".to_string()), v[1].clone(), acc];
+                        continue;
+                    }
+                }
+            }
+        }
+        acc = list![Sym("cons".to_string()), quasiquote(elt), acc];
+    }
+    acc
+}
+
+fn quasiquote(ast: &MalVal) -> MalVal {
+    match ast {
+        List(v, _) => {
+            if v.len() == 2 {
+                if let Sym(ref s) = v[0] {
+                    if s == "unquote" {
+                        return v[1].clone();
+                    }
+                }
+            }
+            qq_iter(v)
+        }
+        Vector(v, _) => list![Sym("vec".to_string()), qq_iter(v)],
+        // maps and sets are rebuilt from their quasiquoted keys and
+        // values, or elements, so these may be unquoted too
+        Hash(hm, _) => {
+            let kvs = hm
+                .iter()
+                .flat_map(|(k, v)| [k.clone(), v.clone()])
+                .collect();
+            list![
+                Sym("apply".to_string()),
+                Sym("hash-map".to_string()),
+                qq_iter(&kvs)
+            ]
+        }
+        Set(s, _) => {
+            let elts = s.iter().cloned().collect();
+            list![Sym("set".to_string()), qq_iter(&elts)]
+        }
+        Sym(_) => list![Sym("quote".to_string()), ast.clone()],
+        _ => ast.clone(),
+    }
+}
+
+// The macro a form calls, if it is a list headed by a symbol bound to one.
+fn macro_fn(ast: &MalVal, env: &Env) -> Option<MalVal> {
+    match ast {
+        List(l, _) => match l.front() {
+            Some(Sym(s)) => match env_get(env, s) {
+                Some(f @ MalFunc { is_macro: true, .. }) => Some(f),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn macroexpand_1(ast: &MalVal, env: &Env) -> MalRet {
+    match (macro_fn(ast, env), ast) {
+        (Some(f), List(l, _)) => f.apply(l.iter().skip(1).cloned().collect()),
+        _ => Ok(ast.clone()),
+    }
+}
+
+fn macroexpand(ast: &MalVal, env: &Env) -> MalRet {
+    let mut ast = ast.clone();
+    while macro_fn(&ast, env).is_some() {
+        ast = macroexpand_1(&ast, env)?;
+    }
+    Ok(ast)
+}
+
+// Values worked out once per form evaluated, keyed by the address of
+// the form's list. The list is held weakly, which keeps the address from
+// being reused while the entry lives, and entries for freed lists are
+// dropped as the cache grows.
+struct SiteCache<T> {
+    sites: FnvHashMap<*const MalSeq, (Weak<MalSeq>, T)>,
+    // prune freed sites when there are this many
+    limit: usize,
+}
+
+impl<T: Clone> SiteCache<T> {
+    fn new() -> SiteCache<T> {
+        SiteCache {
+            sites: FnvHashMap::default(),
+            limit: 0,
+        }
+    }
+
+    fn get(&self, l: &Rc<MalSeq>) -> Option<T> {
+        self.sites.get(&Rc::as_ptr(l)).map(|(_, t)| t.clone())
+    }
+
+    fn insert(&mut self, l: &Rc<MalSeq>, t: T) {
+        if self.sites.len() >= self.limit {
+            self.sites.retain(|_, (site, _)| site.strong_count() > 0);
+            self.limit = 64.max(2 * self.sites.len());
+        }
+        self.sites.insert(Rc::as_ptr(l), (Rc::downgrade(l), t));
+    }
+}
+
+thread_local! {
+    // each macro call's expansion, with the macro that made it, so a
+    // call site whose head has come to name another macro (as when the
+    // macro is redefined) is expanded again
+    static EXPANSIONS: RefCell<SiteCache<(MalVal, MalVal)>> = RefCell::new(SiteCache::new());
+    // what checking each loop* found in its body
+    static LOOPS: RefCell<SiteCache<LoopBody>> = RefCell::new(SiteCache::new());
+}
+
+// Macro expansions and loop* checks are cached unless MACRO-CACHE is
+// false or nil in env.
+fn caching(env: &Env) -> bool {
+    !matches!(env_get(env, "MACRO-CACHE"), Some(Bool(false)) | Some(Nil))
+}
+
+fn same_macro(a: &MalVal, b: &MalVal) -> bool {
+    match (a, b) {
+        (
+            MalFunc {
+                ast: a1, env: e1, ..
+            },
+            MalFunc {
+                ast: a2, env: e2, ..
+            },
+        ) => Rc::ptr_eq(a1, a2) && Rc::ptr_eq(e1, e2),
+        _ => false,
+    }
+}
+
+// Expand a call to the macro f, once per call site when caching.
+fn expand_at(l: &Rc<MalSeq>, f: &MalVal, env: &Env) -> MalRet {
+    let expand = || f.apply(l.iter().skip(1).cloned().collect());
+    if !caching(env) {
+        return expand();
+    }
+    let cached = EXPANSIONS.with(|c| match c.borrow().get(l) {
+        Some((mac, form)) if same_macro(&mac, f) => Some(form),
+        _ => None,
+    });
+    if let Some(form) = cached {
+        return Ok(form);
+    }
+    let form = expand()?;
+    EXPANSIONS.with(|c| c.borrow_mut().insert(l, (f.clone(), form.clone())));
+    Ok(form)
+}
+
+// Rebuild a list or vector with f applied to each element and its index.
+fn map_seq(v: &MalVal, mut f: impl FnMut(usize, &MalVal) -> MalRet) -> MalRet {
+    let (items, meta) = match v {
+        List(items, meta) | Vector(items, meta) => (items, meta),
+        _ => return Ok(v.clone()),
+    };
+    let items = items
+        .iter()
+        .enumerate()
+        .map(|(i, x)| f(i, x))
+        .collect::<Result<MalSeq, MalErr>>()?;
+    match v {
+        List(..) => Ok(List(Rc::new(items), meta.clone())),
+        _ => Ok(Vector(Rc::new(items), meta.clone())),
+    }
+}
+
+// Rebuild a list, vector, map or set with f applied to each element
+// (or key and value), keeping its metadata.
+fn map_elts(v: &MalVal, mut f: impl FnMut(&MalVal) -> MalRet) -> MalRet {
+    match v {
+        List(..) | Vector(..) => map_seq(v, |_, x| f(x)),
+        Hash(hm, meta) => {
+            let mut new_hm = MalMap::default();
+            for (k, v) in hm.iter() {
+                new_hm.insert(f(k)?, f(v)?);
+            }
+            Ok(Hash(Rc::new(new_hm), meta.clone()))
+        }
+        Set(s, meta) => {
+            let mut new_s = MalSet::default();
+            for x in s.iter() {
+                new_s.insert(f(x)?);
+            }
+            Ok(Set(Rc::new(new_s), meta.clone()))
+        }
+        _ => Ok(v.clone()),
+    }
+}
+
+// Expand every macro call in a form, walking into the parts of special
+// forms that are code. Quoted forms are left as they are.
+fn macroexpand_all(ast: &MalVal, env: &Env) -> MalRet {
+    let ast = macroexpand(ast, env)?;
+    // expand the elements from the nth on
+    let from = |n: usize| {
+        move |i: usize, x: &MalVal| {
+            if i < n {
+                Ok(x.clone())
+            } else {
+                macroexpand_all(x, env)
+            }
+        }
+    };
+    match ast {
+        List(ref l, _) => match l.front() {
+            Some(Sym(s)) if s == "quote" => Ok(ast.clone()),
+            Some(Sym(s)) if s == "quasiquote" => map_seq(&ast, |i, x| match i {
+                0 => Ok(x.clone()),
+                _ => expand_unquoted(x, env),
+            }),
+            Some(Sym(s)) if s == "def!" || s == "defmacro!" => map_seq(&ast, from(2)),
+            Some(Sym(s)) if s == "let*" || s == "loop*" => map_seq(&ast, |i, x| match i {
+                0 => Ok(x.clone()),
+                // the bound values, not the patterns
+                1 => map_seq(x, |j, b| match j % 2 {
+                    0 => Ok(b.clone()),
+                    _ => macroexpand_all(b, env),
+                }),
+                _ => macroexpand_all(x, env),
+            }),
+            Some(Sym(s)) if s == "fn*" && is_multi_arity(l) => map_seq(&ast, |i, c| match i {
+                0 => Ok(c.clone()),
+                _ => map_seq(c, from(1)),
+            }),
+            Some(Sym(s)) if s == "fn*" => map_seq(&ast, from(2)),
+            Some(Sym(s)) if s == "try*" => map_seq(&ast, |i, x| match (i, x) {
+                (0, _) => Ok(x.clone()),
+                (1, _) => macroexpand_all(x, env),
+                (_, List(c, _)) if c.front() == Some(&Sym("catch*".to_string())) => {
+                    map_seq(x, from(2))
+                }
+                _ => map_seq(x, from(1)),
+            }),
+            _ => map_seq(&ast, from(0)),
+        },
+        Vector(..) | Hash(..) | Set(..) => map_elts(&ast, |x| macroexpand_all(x, env)),
+        _ => Ok(ast),
+    }
+}
+
+// Expand the macro calls in the unquoted parts of a quasiquoted form,
+// which are evaluated; the rest of it is quoted and left as it is.
+fn expand_unquoted(ast: &MalVal, env: &Env) -> MalRet {
+    let unquote = |l: &MalSeq| match &l[0] {
+        Sym(s) => l.len() == 2 && (s == "unquote" || s == "splice-unquote"),
+        _ => false,
+    };
+    match ast {
+        List(l, _) if !l.is_empty() && unquote(l) => map_seq(ast, |i, x| match i {
+            0 => Ok(x.clone()),
+            _ => macroexpand_all(x, env),
+        }),
+        _ => map_elts(ast, |x| expand_unquoted(x, env)),
+    }
+}
+
+// (fn* ([x] ...) ([x y] ...) ([x & more] ...)): every form after fn* is
+// a clause starting with a parameter list.
+fn is_multi_arity(l: &MalSeq) -> bool {
+    l.len() > 1
+        && l.iter().skip(1).all(|c| match c {
+            List(c, _) => matches!(c.front(), Some(List(_, _)) | Some(Vector(_, _))),
+            _ => false,
+        })
+}
+
+// A function defined with def! or defmacro! takes the name it is defined
+// under, for error messages, unless it already has one (from its :name
+// metadata, or an earlier def!).
+fn named(val: MalVal, sym: &MalVal) -> MalVal {
+    match (val, sym) {
+        (
+            MalFunc {
+                eval,
+                ast,
+                env,
+                params,
+                is_macro,
+                name: None,
+                meta,
+            },
+            Sym(s),
+        ) => MalFunc {
+            eval,
+            ast,
+            env,
+            params,
+            is_macro,
+            name: Some(s.as_str().into()),
+            meta,
+        },
+        (val, _) => val,
+    }
+}
+
+// A catch* binds the stack of the error it caught under this name,
+// which no symbol has, beside the symbol it binds the error to.
+const CAUGHT_STACK: &str = "caught stack";
+
+// The stack of the error caught by the catch* that binds sym, if it is
+// the innermost binding of sym, or nil.
+fn caught_stack(sym: &MalVal, env: &Env) -> MalVal {
+    let found = match sym {
+        Sym(s) => env_find(env, s).and_then(|e| env_get_own(&e, CAUGHT_STACK)),
+        _ => None,
+    };
+    found.unwrap_or(Nil)
+}
+
+// The (catch* sym handler) and (finally* forms...) clauses of a try*,
+// each optional but in that order. They are checked before the body is
+// evaluated, so that a finally* always runs once the body has.
+fn try_clauses(l: &MalSeq) -> Result<(Option<&MalSeq>, Option<&MalSeq>), MalErr> {
+    let clause = |i: usize, head: &str| match l.get(i) {
+        Some(List(c, _)) if c.front() == Some(&Sym(head.to_string())) => Some(&**c),
+        _ => None,
+    };
+    let catch = clause(2, "catch*");
+    let finally = clause(if catch.is_some() { 3 } else { 2 }, "finally*");
+    let n = 2 + catch.is_some() as usize + finally.is_some() as usize;
+    if l.len() < 2 || l.len() > n {
+        return Err(syntax_error("invalid try* clauses"));
+    }
+    if catch.is_some_and(|c| c.len() != 3) {
+        return Err(syntax_error("invalid catch block"));
+    }
+    if catch.is_some_and(|c| !matches!(c[1], Sym(_))) {
+        return Err(syntax_error("catch* must bind a symbol"));
+    }
+    Ok((catch, finally))
+}
+
+// The clauses of a multi-arity fn*, each as a (params body) list with
+// a body of several forms wrapped in a do. No two clauses may take the
+// same number of args, and only one may be variadic, taking at least as
+// many as any other.
+fn fn_clauses(l: &MalSeq, name: Option<&str>) -> MalRet {
+    let mut clauses = vec![];
+    let mut fixed: Vec<usize> = vec![];
+    let mut variadic = None;
+    for c in l.iter().skip(1) {
+        if let List(c, _) = c {
+            match param_count(&c[0]) {
+                (_, true) if variadic.is_some() => {
+                    return Err(syntax_error("fn* can only have one variadic arity"));
+                }
+                (n, true) => variadic = Some(n),
+                (n, false) if fixed.contains(&n) => {
+                    let args = if n == 1 { "arg" } else { "args" };
+                    let msg = format!(
+                        "{} has two clauses taking {} {}",
+                        describe_fn(name),
+                        n,
+                        args
+                    );
+                    return Err(syntax_error(&msg));
+                }
+                (n, false) => fixed.push(n),
+            }
+            let body = match c.len() {
+                1 => Nil,
+                2 => c[1].clone(),
+                _ => {
+                    let mut body = (**c).clone();
+                    body.set(0, Sym("do".to_string()));
+                    List(Rc::new(body), Rc::new(Nil))
+                }
+            };
+            let (params, body) = fn_params(&c[0], &body);
+            clauses.push(list![params, body]);
+        }
+    }
+    match (variadic, fixed.iter().max()) {
+        (Some(v), Some(&n)) if n > v => Err(syntax_error(&format!(
+            "{} has a clause taking {} args, more than its variadic clause's {}",
+            describe_fn(name),
+            n,
+            v
+        ))),
+        _ => Ok(list!(clauses)),
+    }
+}
+
+fn multi_arity_fn(l: &MalSeq, env: &Env, name: Option<&str>) -> MalRet {
+    Ok(MalFunc {
+        eval,
+        ast: Rc::new(fn_clauses(l, name)?),
+        env: env.clone(),
+        params: Rc::new(Nil),
+        is_macro: false,
+        name: None,
+        meta: Rc::new(Nil),
+    })
+}
+
+// The value def! or defmacro! binds to sym, named after it. A
+// multi-arity fn* is checked knowing the name, to report it in errors.
+fn eval_named(form: &MalVal, sym: &MalVal, env: &Env) -> MalRet {
+    let name = match sym {
+        Sym(s) => Some(&s[..]),
+        _ => None,
+    };
+    let val = match form {
+        List(l, _) if l.front() == Some(&Sym("fn*".to_string())) && is_multi_arity(l) => {
+            multi_arity_fn(l, env, name)?
+        }
+        _ => eval(form, env)?,
+    };
+    Ok(named(val, sym))
+}
+
+// A loop* is checked before it runs: each recur in its body must be in
+// tail position, where it ends an iteration, and give a value for each
+// binding. Whether the body makes closures (with fn* or lazy-seq) is
+// noted too. These may hold on to the environment of an iteration, so a
+// recur must bind the next values in a new environment, where otherwise
+// it rebinds them in place.
+fn check_loop(ast: &MalVal, l: &Rc<MalSeq>, env: &Env) -> Result<bool, MalErr> {
+    let cache = caching(env);
+    let cached = LOOPS.with(|c| c.borrow().get(l)).filter(|seen| {
+        let unchanged = |(s, mac): &(String, MalVal)| match env_get(env, s) {
+            Some(f) => same_macro(&f, mac),
+            None => false,
+        };
+        cache && seen.macros.iter().all(unchanged)
+    });
+    if let Some(seen) = cached {
+        return Ok(seen.closes);
+    }
+    let mut seen = LoopBody::default();
+    check_recur(ast, env, None, false, &mut seen)?;
+    if cache {
+        LOOPS.with(|c| c.borrow_mut().insert(l, seen.clone()));
+    }
+    Ok(seen.closes)
+}
+
+// What checking a loop* found in its body: whether it makes closures,
+// and the macros it was expanded through, by the names they were called
+// by. A cached check is only good while each name means the same macro.
+#[derive(Clone, Default)]
+struct LoopBody {
+    closes: bool,
+    macros: Vec<(String, MalVal)>,
+}
+
+fn recur_count_error(expected: usize, got: usize) -> MalErr {
+    let values = if expected == 1 { "value" } else { "values" };
+    syntax_error(&format!(
+        "recur expected {} {}, got {}",
+        expected, values, got
+    ))
+}
+
+// Check the recurs in a form: recurs is the number of values a recur
+// takes where the form is, or None outside of any loop*, and tail whether
+// the form is in tail position. Macro calls are checked as expanded.
+fn check_recur(
+    ast: &MalVal,
+    env: &Env,
+    recurs: Option<usize>,
+    tail: bool,
+    seen: &mut LoopBody,
+) -> Result<(), MalErr> {
+    let mut check =
+        |x: &MalVal, recurs: Option<usize>, tail: bool| check_recur(x, env, recurs, tail, seen);
+    let (l, meta) = match ast {
+        List(l, meta) => (l, meta),
+        Vector(v, _) => return v.iter().try_for_each(|x| check(x, recurs, false)),
+        Hash(hm, _) => {
+            return hm
+                .iter()
+                .try_for_each(|(k, v)| check(k, recurs, false).and(check(v, recurs, false)))
+        }
+        Set(s, _) => return s.iter().try_for_each(|x| check(x, recurs, false)),
+        _ => return Ok(()),
+    };
+    match l.front() {
+        Some(Sym(s)) if s == "quote" => Ok(()),
+        Some(Sym(s)) if s == "quasiquote" && l.len() > 1 => check(&quasiquote(&l[1]), recurs, tail),
+        Some(Sym(s)) if s == "recur" => match recurs {
+            _ if !tail => Err(syntax_error("recur must be in tail position").at(meta)),
+            None => Err(syntax_error("recur outside of loop*").at(meta)),
+            Some(n) if n != l.len() - 1 => Err(recur_count_error(n, l.len() - 1).at(meta)),
+            _ => l.iter().skip(1).try_for_each(|x| check(x, recurs, false)),
+        },
+        Some(Sym(s)) if s == "if" => {
+            let mut branches = l.iter().enumerate().skip(1);
+            branches.try_for_each(|(i, x)| check(x, recurs, tail && i > 1))
+        }
+        Some(Sym(s)) if s == "do" => {
+            let mut forms = l.iter().enumerate().skip(1);
+            forms.try_for_each(|(i, x)| check(x, recurs, tail && i == l.len() - 1))
+        }
+        // the bound values, not the patterns
+        Some(Sym(s)) if s == "let*" || s == "loop*" => {
+            let binds = match l.get(1) {
+                Some(List(b, _)) | Some(Vector(b, _)) => (**b).clone(),
+                _ => MalSeq::new(),
+            };
+            for x in binds.iter().skip(1).step_by(2) {
+                check(x, recurs, false)?;
+            }
+            match l.get(2) {
+                Some(body) if s == "loop*" => check(body, Some(binds.len() / 2), true),
+                Some(body) => check(body, recurs, tail),
+                None => Ok(()),
+            }
+        }
+        Some(Sym(s)) if s == "fn*" && is_multi_arity(l) => {
+            seen.closes = true;
+            for c in l.iter().skip(1) {
+                if let List(c, _) = c {
+                    c.iter()
+                        .skip(1)
+                        .try_for_each(|x| check_recur(x, env, None, true, seen))?;
+                }
+            }
+            Ok(())
+        }
+        Some(Sym(s)) if s == "fn*" || s == "lazy-seq" => {
+            seen.closes = true;
+            let mut body = l.iter().skip(if s == "fn*" { 2 } else { 1 });
+            body.try_for_each(|x| check_recur(x, env, None, true, seen))
+        }
+        Some(Sym(s)) => match macro_fn(ast, env) {
+            Some(f) => {
+                let form = expand_at(l, &f, env)?;
+                seen.macros.push((s.clone(), f));
+                check_recur(&form, env, recurs, tail, seen)
+            }
+            None => l.iter().try_for_each(|x| check(x, recurs, false)),
+        },
+        _ => l.iter().try_for_each(|x| check(x, recurs, false)),
+    }
+}
+
+// The loop* whose body is being evaluated, for a recur to go back to.
+struct Loop {
+    // the environment the loop* was evaluated in, and the one it bound
+    outer: Env,
+    env: Env,
+    patterns: Vec<MalVal>,
+    body: MalVal,
+    closes: bool,
+}
+
+// An error is located at the innermost list being evaluated that was
+// read from a file, and so has its place in the file as metadata. If
+// the evaluation ended in a (tail) call to a mal function, the error
+// came out of that call.
+pub fn eval(ast: &MalVal, env: &Env) -> MalRet {
+    let mut here = Rc::new(Nil);
+    let mut callee = None;
+    eval_form(ast, env, &mut here, &mut callee).map_err(|e| match callee {
+        Some((name, site)) => e.at(&here).called(name, &site),
+        None => e.at(&here),
+    })
+}
+
+fn eval_form(
+    orig_ast: &MalVal,
+    orig_env: &Env,
+    here: &mut Rc<MalVal>,
+    callee: &mut Option<(Option<Rc<str>>, Rc<MalVal>)>,
+) -> MalRet {
+    let mut ast = orig_ast;
+    let mut env = orig_env;
+    // These variables ensure a sufficient lifetime for the data
+    // referenced by ast and env.
+    let mut live_ast;
+    let mut live_env;
+    // the innermost loop* the form being evaluated is in tail position in
+    let mut looping: Option<Loop> = None;
+
+    'tco: loop {
+        match env_get(env, "DEBUG-EVAL") {
+            None | Some(Bool(false)) | Some(Nil) => (),
+            _ => println!("EVAL: {}", ast.pr_str(true)),
+        }
+        match ast {
+            Sym(s) => match env_get(env, s) {
+                Some(r) => return Ok(r),
+                None => {
+                    let msg = format!("'{}' not found", s);
+                    return Err(ex_error(
+                        "unknown-symbol",
+                        &msg,
+                        vec![("symbol", ast.clone())],
+                    ));
+                }
+            },
+            Vector(v, _) => {
+                let mut lst: MalArgs = vec![];
+                for a in v.iter() {
+                    lst.push(eval(a, env)?);
+                }
+                return Ok(vector!(lst));
+            }
+            Hash(hm, _) => {
+                let mut kvs: MalArgs = vec![];
+                for (k, v) in hm.iter() {
+                    kvs.push(eval(k, env)?);
+                    kvs.push(eval(v, env)?);
+                }
+                return hash_map(kvs);
+            }
+            Set(s, _) => {
+                let mut items: MalArgs = vec![];
+                for a in s.iter() {
+                    items.push(eval(a, env)?);
+                }
+                return hash_set(items);
+            }
+            List(l, meta) => {
+                if l.is_empty() {
+                    return Ok(ast.clone());
+                }
+                if !matches!(**meta, Nil) {
+                    *here = meta.clone();
+                }
+                let a0 = &l[0];
+                match a0 {
+                    Sym(a0sym) if a0sym == "def!" => {
+                        return env_set(env, &l[1], eval_named(&l[2], &l[1], env)?);
+                    }
+                    Sym(a0sym) if a0sym == "let*" => {
+                        live_env = env_new(Some(env.clone()));
+                        env = &live_env;
+                        let (a1, a2) = (&l[1], &l[2]);
+                        match a1 {
+                            List(binds, _) | Vector(binds, _) => {
+                                for (b, e) in binds.iter().tuples() {
+                                    let val = eval(e, env)?;
+                                    bind(env, b, val)?;
+                                }
+                            }
+                            _ => {
+                                return Err(syntax_error("let* with non-List bindings"));
+                            }
+                        };
+                        live_ast = a2.clone();
+                        ast = &live_ast;
+                        continue 'tco;
+                    }
+                    Sym(a0sym) if a0sym == "loop*" => {
+                        let closes = check_loop(ast, l, env)?;
+                        let binds = match l.get(1) {
+                            Some(List(binds, _)) | Some(Vector(binds, _)) => binds.clone(),
+                            _ => return Err(syntax_error("loop* with non-List bindings")),
+                        };
+                        let loop_env = env_new(Some(env.clone()));
+                        for (b, e) in binds.iter().tuples() {
+                            let val = eval(e, &loop_env)?;
+                            bind(&loop_env, b, val)?;
+                        }
+                        let body = l.get(2).cloned().unwrap_or(Nil);
+                        looping = Some(Loop {
+                            outer: env.clone(),
+                            env: loop_env.clone(),
+                            patterns: binds.iter().step_by(2).cloned().collect(),
+                            body: body.clone(),
+                            closes,
+                        });
+                        live_env = loop_env;
+                        env = &live_env;
+                        live_ast = body;
+                        ast = &live_ast;
+                        continue 'tco;
+                    }
+                    Sym(a0sym) if a0sym == "recur" => {
+                        let lp = match &looping {
+                            Some(lp) => lp,
+                            None => return Err(syntax_error("recur outside of loop*")),
+                        };
+                        if l.len() - 1 != lp.patterns.len() {
+                            return Err(recur_count_error(lp.patterns.len(), l.len() - 1));
+                        }
+                        let mut vals: MalArgs = vec![];
+                        for x in l.iter().skip(1) {
+                            vals.push(eval(x, env)?);
+                        }
+                        live_env = if lp.closes {
+                            env_new(Some(lp.outer.clone()))
+                        } else {
+                            lp.env.clone()
+                        };
+                        for (p, v) in lp.patterns.iter().zip(vals) {
+                            bind(&live_env, p, v)?;
+                        }
+                        env = &live_env;
+                        live_ast = lp.body.clone();
+                        ast = &live_ast;
+                        continue 'tco;
+                    }
+                    Sym(a0sym) if a0sym == "quote" => return Ok(l[1].clone()),
+                    Sym(a0sym) if a0sym == "quasiquote" => {
+                        live_ast = quasiquote(&l[1]);
+                        ast = &live_ast;
+                        continue 'tco;
+                    }
+                    Sym(a0sym) if a0sym == "defmacro!" => {
+                        let (a1, a2) = (&l[1], &l[2]);
+                        let r = eval_named(a2, a1, env)?;
+                        match r {
+                            MalFunc {
+                                eval,
+                                ast,
+                                env,
+                                params,
+                                name,
+                                ..
+                            } => {
+                                return env_set(
+                                    &env,
+                                    a1,
+                                    MalFunc {
+                                        eval,
+                                        ast,
+                                        env: env.clone(),
+                                        params,
+                                        is_macro: true,
+                                        name,
+                                        meta: Rc::new(Nil),
+                                    },
+                                )
+                            }
+                            _ => return Err(type_error("set_macro on non-function", &r)),
+                        }
+                    }
+                    // (ex-stack e) in a catch* that binds e
+                    Sym(a0sym) if a0sym == "ex-stack" => {
+                        if l.len() != 2 {
+                            return Err(syntax_error("ex-stack takes one form"));
+                        }
+                        eval(&l[1], env)?;
+                        return Ok(caught_stack(&l[1], env));
+                    }
+                    // these take a (quoted) form, as functions would, but
+                    // expand it in the environment they are evaluated in
+                    Sym(a0sym)
+                        if a0sym == "macroexpand-1"
+                            || a0sym == "macroexpand"
+                            || a0sym == "macroexpand-all" =>
+                    {
+                        if l.len() != 2 {
+                            return Err(syntax_error(&format!("{} takes one form", a0sym)));
+                        }
+                        let form = eval(&l[1], env)?;
+                        return match &a0sym[..] {
+                            "macroexpand-1" => macroexpand_1(&form, env),
+                            "macroexpand" => macroexpand(&form, env),
+                            _ => macroexpand_all(&form, env),
+                        };
+                    }
+                    Sym(a0sym) if a0sym == "try*" => {
+                        // a recur cannot leave a try*
+                        looping = None;
+                        let (catch, finally) = try_clauses(l)?;
+                        let finally = match (catch, finally) {
+                            (None, None) => {
+                                live_ast = l[1].clone();
+                                ast = &live_ast;
+                                continue 'tco;
+                            }
+                            // without a finally*, the handler is a tail call
+                            (Some(c), None) => match eval(&l[1], env) {
+                                Err(e) => {
+                                    let (sym, handler) = (c[1].clone(), c[2].clone());
+                                    live_env = env_new(Some(env.clone()));
+                                    env = &live_env;
+                                    env_sets(env, CAUGHT_STACK, e.stack_value());
+                                    env_set(env, &sym, e.into_value())?;
+                                    live_ast = handler;
+                                    ast = &live_ast;
+                                    continue 'tco;
+                                }
+                                res => return res,
+                            },
+                            (_, Some(f)) => f,
+                        };
+                        let res = match (eval(&l[1], env), catch) {
+                            (Err(e), Some(c)) => {
+                                let catch_env = env_new(Some(env.clone()));
+                                env_sets(&catch_env, CAUGHT_STACK, e.stack_value());
+                                env_set(&catch_env, &c[1], e.into_value())?;
+                                eval(&c[2], &catch_env)
+                            }
+                            (res, _) => res,
+                        };
+                        for form in finally.iter().skip(1) {
+                            eval(form, env)?;
+                        }
+                        return res;
+                    }
+                    Sym(a0sym) if a0sym == "do" => {
+                        for i in 1..l.len() - 1 {
+                            let _ = eval(&l[i], env)?;
+                        }
+                        live_ast = if l.len() > 1 {
+                            l[l.len() - 1].clone()
+                        } else {
+                            Nil
+                        };
+                        ast = &live_ast;
+                        continue 'tco;
+                    }
+                    Sym(a0sym) if a0sym == "if" => {
+                        let cond = eval(&l[1], env)?;
+                        match cond {
+                            Bool(false) | Nil if l.len() >= 4 => {
+                                live_ast = l[3].clone();
+                                ast = &live_ast;
+                                continue 'tco;
+                            }
+                            Bool(false) | Nil => return Ok(Nil),
+                            _ if l.len() >= 3 => {
+                                live_ast = l[2].clone();
+                                ast = &live_ast;
+                                continue 'tco;
+                            }
+                            _ => return Ok(Nil),
+                        }
+                    }
+                    Sym(a0sym) if a0sym == "fn*" && is_multi_arity(l) => {
+                        return multi_arity_fn(l, env, None);
+                    }
+                    Sym(a0sym) if a0sym == "fn*" => {
+                        let (a1, a2) = fn_params(&l[1], &l[2]);
+                        return Ok(MalFunc {
+                            eval,
+                            ast: Rc::new(a2),
+                            env: env.clone(),
+                            params: Rc::new(a1),
+                            is_macro: false,
+                            name: None,
+                            meta: Rc::new(Nil),
+                        });
+                    }
+                    Sym(a0sym) if a0sym == "lazy-seq" => {
+                        // the body becomes a function of no arguments,
+                        // called the first time the seq is looked at
+                        let mut body = (**l).clone();
+                        body.set(0, Sym("do".to_string()));
+                        let thunk = MalFunc {
+                            eval,
+                            ast: Rc::new(List(Rc::new(body), Rc::new(Nil))),
+                            env: env.clone(),
+                            params: Rc::new(list![]),
+                            is_macro: false,
+                            name: None,
+                            meta: Rc::new(Nil),
+                        };
+                        return Ok(lazy_seq(thunk, vec![]));
+                    }
+                    Sym(a0sym) if a0sym == "eval" => {
+                        //  Hard to implement without global variables.
+                        //  Normal argument evaluation.
+                        live_ast = eval(&l[1], env)?;
+                        ast = &live_ast;
+                        looping = None;
+                        live_env = env_find_repl(env);
+                        env = &live_env;
+                        continue 'tco;
+                    }
+                    _ => match eval(a0, env) {
+                        Ok(f @ MalFunc { is_macro: true, .. }) => {
+                            live_ast = expand_at(l, &f, env)?;
+                            ast = &live_ast;
+                            continue 'tco;
+                        }
+                        Ok(f @ Func(_, _)) => {
+                            let mut args: MalArgs = vec![];
+                            for i in 1..l.len() {
+                                args.push(eval(&l[i], env)?);
+                            }
+                            return f.apply(args);
+                        }
+                        Ok(MalFunc {
+                            ast: mast,
+                            env: menv,
+                            params: mparams,
+                            name: mname,
+                            ..
+                        }) => {
+                            let mut args: MalArgs = vec![];
+                            for i in 1..l.len() {
+                                args.push(eval(&l[i], env)?);
+                            }
+                            let name = mname.as_deref();
+                            let (params, body) = fn_clause(name, &mparams, &mast, args.len())?;
+                            live_env = env_bind(Some(menv.clone()), params, args, name)?;
+                            *callee = Some((mname, here.clone()));
+                            looping = None;
+                            *here = Rc::new(Nil);
+                            env = &live_env;
+                            live_ast = body.clone();
+                            ast = &live_ast;
+                            continue 'tco;
+                        }
+                        Ok(f) => return Err(type_error("attempt to call non-function", &f)),
+                        e @ Err(_) => return e,
+                    },
+                }
+            }
+            _ => return Ok(ast.clone()),
+        };
+    } // end 'tco loop
+}
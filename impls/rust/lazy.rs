@@ -0,0 +1,188 @@
+use std::cmp::Ordering;
+
+use crate::numeric;
+use crate::types::MalVal::{Bool, Int, Nil, Str};
+use crate::types::{ex_error, func, lazy_cons, lazy_seq, type_error, MalArgs, MalRet, MalVal};
+
+// Lazy sequence builtins. Each one returns a pending lazy seq whose
+// function is one of the *_step builtins below, with the state it needs
+// passed along as arguments. Realizing a cell yields its first element
+// and a new pending cell holding the state for the rest, so nothing past
+// the elements actually consumed is ever computed.
+
+fn truthy(v: &MalVal) -> bool {
+    !matches!(v, Nil | Bool(false))
+}
+
+pub fn range(a: MalArgs) -> MalRet {
+    let (start, end, step) = match a.len() {
+        0 => (Int(0), Nil, Int(1)),
+        1 => (Int(0), a[0].clone(), Int(1)),
+        2 => (a[0].clone(), a[1].clone(), Int(1)),
+        3 => (a[0].clone(), a[1].clone(), a[2].clone()),
+        n => {
+            let msg = format!("wrong number of args to range (got {})", n);
+            let data = vec![("fn", Str("range".to_string())), ("got", Int(n as i64))];
+            return Err(ex_error("arity", &msg, data));
+        }
+    };
+    for x in [&start, &end, &step] {
+        if *x != Nil && numeric::to_f64(x).is_none() {
+            return Err(type_error(
+                &format!("range: {} is not a number", x.pr_str(true)),
+                x,
+            ));
+        }
+    }
+    Ok(lazy_seq(func(range_step), vec![start, end, step]))
+}
+
+// A nil end makes the range infinite.
+fn range_step(a: MalArgs) -> MalRet {
+    let (start, end, step) = (&a[0], &a[1], &a[2]);
+    if *end != Nil {
+        let toward = match numeric::cmp(step, &Int(0)) {
+            Ok(Some(Ordering::Less)) => Ordering::Greater,
+            _ => Ordering::Less,
+        };
+        if numeric::cmp(start, end) != Ok(Some(toward)) {
+            return Ok(Nil);
+        }
+    }
+    let next = numeric::add(start, step)?;
+    Ok(lazy_cons(
+        start.clone(),
+        lazy_seq(func(range_step), vec![next, end.clone(), step.clone()]),
+    ))
+}
+
+pub fn iterate(a: MalArgs) -> MalRet {
+    Ok(lazy_seq(func(iterate_step), a))
+}
+
+// (f x) is only called once the element after x is needed.
+fn iterate_step(a: MalArgs) -> MalRet {
+    Ok(lazy_cons(a[1].clone(), lazy_seq(func(iterate_next), a)))
+}
+
+fn iterate_next(a: MalArgs) -> MalRet {
+    let next = a[0].apply(vec![a[1].clone()])?;
+    iterate_step(vec![a[0].clone(), next])
+}
+
+pub fn repeat(a: MalArgs) -> MalRet {
+    match a.len() {
+        1 => Ok(lazy_seq(func(repeat_step), a)),
+        _ => take(vec![a[0].clone(), repeat(vec![a[1].clone()])?]),
+    }
+}
+
+fn repeat_step(a: MalArgs) -> MalRet {
+    Ok(lazy_cons(a[0].clone(), lazy_seq(func(repeat_step), a)))
+}
+
+pub fn cycle(a: MalArgs) -> MalRet {
+    Ok(lazy_seq(func(cycle_step), vec![a[0].clone(), a[0].clone()]))
+}
+
+// Walks the current pass over the collection, starting a new pass from
+// the original when it runs out.
+fn cycle_step(a: MalArgs) -> MalRet {
+    let (orig, cur) = (&a[0], &a[1]);
+    let (x, rest) = match cur.uncons()? {
+        Some(c) => c,
+        None => match orig.uncons()? {
+            Some(c) => c,
+            None => return Ok(Nil),
+        },
+    };
+    Ok(lazy_cons(
+        x,
+        lazy_seq(func(cycle_step), vec![orig.clone(), rest]),
+    ))
+}
+
+pub fn take(a: MalArgs) -> MalRet {
+    match a[0] {
+        Int(_) => Ok(lazy_seq(func(take_step), a)),
+        _ => Err(type_error("take: count must be an integer", &a[0])),
+    }
+}
+
+fn take_step(a: MalArgs) -> MalRet {
+    match a[0] {
+        Int(n) if n > 0 => match a[1].uncons()? {
+            Some((x, rest)) => Ok(lazy_cons(
+                x,
+                lazy_seq(func(take_step), vec![Int(n - 1), rest]),
+            )),
+            None => Ok(Nil),
+        },
+        _ => Ok(Nil),
+    }
+}
+
+pub fn drop(a: MalArgs) -> MalRet {
+    match a[0] {
+        Int(_) => Ok(lazy_seq(func(drop_step), a)),
+        _ => Err(type_error("drop: count must be an integer", &a[0])),
+    }
+}
+
+fn drop_step(a: MalArgs) -> MalRet {
+    let mut s = a[1].clone();
+    if let Int(n) = a[0] {
+        for _ in 0..n {
+            match s.uncons()? {
+                Some((_, rest)) => s = rest,
+                None => return Ok(Nil),
+            }
+        }
+    }
+    Ok(s)
+}
+
+pub fn take_while(a: MalArgs) -> MalRet {
+    Ok(lazy_seq(func(take_while_step), a))
+}
+
+fn take_while_step(a: MalArgs) -> MalRet {
+    let pred = &a[0];
+    match a[1].uncons()? {
+        Some((x, rest)) if truthy(&pred.apply(vec![x.clone()])?) => Ok(lazy_cons(
+            x,
+            lazy_seq(func(take_while_step), vec![pred.clone(), rest]),
+        )),
+        _ => Ok(Nil),
+    }
+}
+
+pub fn map(a: MalArgs) -> MalRet {
+    Ok(lazy_seq(func(map_step), a))
+}
+
+fn map_step(a: MalArgs) -> MalRet {
+    let f = &a[0];
+    match a[1].uncons()? {
+        Some((x, rest)) => Ok(lazy_cons(
+            f.apply(vec![x])?,
+            lazy_seq(func(map_step), vec![f.clone(), rest]),
+        )),
+        None => Ok(Nil),
+    }
+}
+
+pub fn concat(a: MalArgs) -> MalRet {
+    Ok(lazy_seq(func(concat_step), a))
+}
+
+fn concat_step(mut a: MalArgs) -> MalRet {
+    while !a.is_empty() {
+        if let Some((x, rest)) = a[0].uncons()? {
+            a[0] = rest;
+            return Ok(lazy_cons(x, lazy_seq(func(concat_step), a)));
+        }
+        a.remove(0);
+    }
+    Ok(Nil)
+}
@@ -0,0 +1,144 @@
+// The full mal interpreter (the evaluator of stepA_mal) as a library,
+// for embedding mal as a scripting layer in a Rust program:
+//
+//     let mal = Interpreter::new();
+//     mal.define("greeting", Str("hello".to_string()));
+//     let f = mal.eval_str("(fn* [x] (str greeting \" \" x))")?;
+//     let s = mal.call(&f, vec![Str("world".to_string())])?;
+//
+// Errors, including exceptions thrown by mal code, come back as a MalErr.
+
+#[macro_use]
+extern crate lazy_static;
+extern crate fnv;
+extern crate im_rc;
+extern crate itertools;
+extern crate num_bigint;
+extern crate num_rational;
+extern crate num_traits;
+extern crate regex;
+extern crate rustyline;
+#[cfg(feature = "serde")]
+extern crate serde;
+
+use std::path::Path;
+use std::rc::Rc;
+
+#[macro_use]
+pub mod types;
+pub mod core;
+mod destructure;
+pub mod env;
+mod eval;
+mod lazy;
+mod numeric;
+pub mod printer;
+pub mod reader;
+#[cfg(feature = "serde")]
+mod serde_value;
+
+use crate::env::{env_find_repl, env_get, env_new, env_sets, Env};
+pub use crate::eval::eval;
+#[cfg(feature = "serde")]
+pub use crate::serde_value::{from_mal, to_mal};
+use crate::types::MalVal::{Func, Nil, Str};
+use crate::types::{error, ArgType, Arity, Builtin, MalArgs, MalErr, MalRet, MalVal};
+
+// core.mal: defined using the language itself
+const PRELUDE: &[&str] = &[
+    "(def! *host-language* \"rust\")",
+    "(def! not (fn* (a) (if a false true)))",
+    "(defmacro! cond (fn* (& xs) (if (> (count xs) 0) (list 'if (first xs) (if (> (count xs) 1) (nth xs 1) (throw \"odd number of forms to cond\")) (cons 'cond (rest (rest xs)))))))",
+];
+
+pub struct Interpreter {
+    env: Env,
+}
+
+impl Interpreter {
+    // A root environment holding the core builtins and prelude, with an
+    // empty *ARGV*.
+    pub fn new() -> Interpreter {
+        let env = env_new(None);
+        for (k, v) in core::ns() {
+            env_sets(&env, k, v);
+        }
+        env_sets(&env, "*ARGV*", list![]);
+        // load-file reads the file itself, to record its name in the
+        // forms read. It holds the environment weakly, as the
+        // environment holds it.
+        let root = Rc::downgrade(&env);
+        let load_file = Builtin {
+            name: "load-file".to_string(),
+            arity: Some(Arity::exactly(1)),
+            types: vec![ArgType::Str],
+            f: Rc::new(move |a| match (&a[0], root.upgrade()) {
+                (Str(path), Some(env)) => load(path, &env).map(|_| Nil),
+                _ => error("load-file: interpreter is gone"),
+            }),
+        };
+        env_sets(&env, "load-file", Func(Rc::new(load_file), Rc::new(Nil)));
+        let mal = Interpreter { env };
+        for src in PRELUDE {
+            if mal.eval_str(src).is_err() {
+                panic!("error during startup");
+            }
+        }
+        mal
+    }
+
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    pub fn eval(&self, ast: &MalVal) -> MalRet {
+        eval(ast, &self.env)
+    }
+
+    // Read and evaluate every form in src, returning the value of the
+    // last one (nil if there are none).
+    pub fn eval_str(&self, src: &str) -> MalRet {
+        self.eval(&reader::read_str(&format!("(do {}\n)", src))?)
+    }
+
+    // Evaluate every form in a file, returning the value of the last one.
+    // Errors are reported with where in the file they were raised.
+    pub fn eval_file<P: AsRef<Path>>(&self, path: P) -> MalRet {
+        load(&path.as_ref().to_string_lossy(), &self.env)
+    }
+
+    // Bind name in the root environment, e.g. to a builtin made with
+    // types::builtin that closes over host state.
+    pub fn define(&self, name: &str, value: MalVal) {
+        env_sets(&self.env, name, value)
+    }
+
+    pub fn get(&self, name: &str) -> Option<MalVal> {
+        env_get(&self.env, name)
+    }
+
+    // Call a mal function or builtin with already evaluated arguments.
+    pub fn call(&self, f: &MalVal, args: MalArgs) -> MalRet {
+        f.apply(args)
+    }
+
+    pub fn set_argv(&self, args: Vec<String>) {
+        self.define(
+            "*ARGV*",
+            list!(args.into_iter().map(Str).collect::<MalArgs>()),
+        )
+    }
+}
+
+fn load(path: &str, env: &Env) -> MalRet {
+    match std::fs::read_to_string(path) {
+        Ok(src) => eval(&reader::read_file(&src, path)?, &env_find_repl(env)),
+        Err(e) => Err(MalErr::ErrString(e.to_string())),
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        Interpreter::new()
+    }
+}
@@ -0,0 +1,271 @@
+use std::cmp::Ordering;
+
+use num_bigint::BigInt as Big;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, ToPrimitive, Zero};
+
+use crate::types::MalVal::{BigInt, Bool, Float, Int, Ratio, Str};
+use crate::types::{ex_error, from_big, from_ratio, type_error, MalArgs, MalRet, MalVal};
+
+// The numeric tower: Int -> BigInt -> Ratio -> Float. Binary
+// operations promote both operands to the wider of the two types before
+// operating. Int operations that overflow are retried on BigInts, and
+// exact results are demoted back to the narrowest type that holds them
+// (whole ratios become integers, BigInts that fit become Ints) so equal
+// values always have the same representation.
+
+enum Pair {
+    Ints(i64, i64),
+    Bigs(Big, Big),
+    Ratios(BigRational, BigRational),
+    Floats(f64, f64),
+}
+
+fn pair(a: &MalVal, b: &MalVal) -> Result<Pair, MalVal> {
+    match (a, b) {
+        (Int(i), Int(j)) => Ok(Pair::Ints(*i, *j)),
+        (Float(_), _) | (_, Float(_)) => match (to_f64(a), to_f64(b)) {
+            (Some(x), Some(y)) => Ok(Pair::Floats(x, y)),
+            (None, _) => Err(a.clone()),
+            (_, None) => Err(b.clone()),
+        },
+        (Ratio(_), _) | (_, Ratio(_)) => match (to_ratio(a), to_ratio(b)) {
+            (Some(x), Some(y)) => Ok(Pair::Ratios(x, y)),
+            (None, _) => Err(a.clone()),
+            (_, None) => Err(b.clone()),
+        },
+        _ => match (to_big(a), to_big(b)) {
+            (Some(x), Some(y)) => Ok(Pair::Bigs(x, y)),
+            (None, _) => Err(a.clone()),
+            (_, None) => Err(b.clone()),
+        },
+    }
+}
+
+// Arithmetic faults are thrown as an ex-info map so that catch* handlers
+// can inspect them, e.g.
+// {:type :arithmetic :op "/" :operands (1 0) :ex/message "Divide by zero"}
+fn arith_error(op: &str, msg: &str, operands: MalArgs) -> MalRet {
+    Err(ex_error(
+        "arithmetic",
+        msg,
+        vec![("op", Str(op.to_string())), ("operands", list!(operands))],
+    ))
+}
+
+fn not_a_number(op: &str, v: &MalVal) -> MalRet {
+    Err(type_error(
+        &format!("{}: {} is not a number", op, v.pr_str(true)),
+        v,
+    ))
+}
+
+fn to_big(a: &MalVal) -> Option<Big> {
+    match a {
+        Int(i) => Some(Big::from(*i)),
+        BigInt(b) => Some(b.clone()),
+        _ => None,
+    }
+}
+
+fn to_ratio(a: &MalVal) -> Option<BigRational> {
+    match a {
+        Ratio(r) => Some(r.clone()),
+        _ => to_big(a).map(BigRational::from_integer),
+    }
+}
+
+pub fn to_f64(a: &MalVal) -> Option<f64> {
+    match a {
+        Int(i) => Some(*i as f64),
+        BigInt(b) => b.to_f64(),
+        Ratio(r) => r.to_f64(),
+        Float(f) => Some(*f),
+        _ => None,
+    }
+}
+
+// Apply an i64 operation, falling back to the BigInt one on overflow.
+fn int_op(
+    i: i64,
+    j: i64,
+    checked: fn(i64, i64) -> Option<i64>,
+    big: fn(Big, Big) -> Big,
+) -> MalVal {
+    match checked(i, j) {
+        Some(r) => Int(r),
+        None => from_big(big(Big::from(i), Big::from(j))),
+    }
+}
+
+pub fn add(a: &MalVal, b: &MalVal) -> MalRet {
+    match pair(a, b) {
+        Ok(Pair::Ints(i, j)) => Ok(int_op(i, j, i64::checked_add, |x, y| x + y)),
+        Ok(Pair::Bigs(x, y)) => Ok(from_big(x + y)),
+        Ok(Pair::Ratios(x, y)) => Ok(from_ratio(x + y)),
+        Ok(Pair::Floats(x, y)) => Ok(Float(x + y)),
+        Err(v) => not_a_number("+", &v),
+    }
+}
+
+pub fn sub(a: &MalVal, b: &MalVal) -> MalRet {
+    match pair(a, b) {
+        Ok(Pair::Ints(i, j)) => Ok(int_op(i, j, i64::checked_sub, |x, y| x - y)),
+        Ok(Pair::Bigs(x, y)) => Ok(from_big(x - y)),
+        Ok(Pair::Ratios(x, y)) => Ok(from_ratio(x - y)),
+        Ok(Pair::Floats(x, y)) => Ok(Float(x - y)),
+        Err(v) => not_a_number("-", &v),
+    }
+}
+
+pub fn mul(a: &MalVal, b: &MalVal) -> MalRet {
+    match pair(a, b) {
+        Ok(Pair::Ints(i, j)) => Ok(int_op(i, j, i64::checked_mul, |x, y| x * y)),
+        Ok(Pair::Bigs(x, y)) => Ok(from_big(x * y)),
+        Ok(Pair::Ratios(x, y)) => Ok(from_ratio(x * y)),
+        Ok(Pair::Floats(x, y)) => Ok(Float(x * y)),
+        Err(v) => not_a_number("*", &v),
+    }
+}
+
+// The quotient of a pair of numbers, or None for an exact division by
+// zero. Floats divide by zero to an infinity or NaN.
+fn quotient(p: Pair) -> Option<MalVal> {
+    match p {
+        Pair::Ints(_, 0) => None,
+        Pair::Ints(i, j) => Some(match i.checked_rem(j) {
+            Some(0) => int_op(i, j, i64::checked_div, |x, y| x / y),
+            _ => from_ratio(BigRational::new(Big::from(i), Big::from(j))),
+        }),
+        Pair::Bigs(_, y) if y.is_zero() => None,
+        Pair::Bigs(x, y) => Some(from_ratio(BigRational::new(x, y))),
+        Pair::Ratios(_, y) if y.is_zero() => None,
+        Pair::Ratios(x, y) => Some(from_ratio(x / y)),
+        Pair::Floats(x, y) => Some(Float(x / y)),
+    }
+}
+
+// (/ x) inverts x, and longer forms divide from the left. A division by
+// zero reports the arguments of the whole call, as they were written.
+pub fn divide(a: MalArgs) -> MalRet {
+    let (mut acc, divisors) = match &a[..] {
+        [] => return Ok(Int(1)),
+        [x] => (Int(1), std::slice::from_ref(x)),
+        [x, rest @ ..] => (x.clone(), rest),
+    };
+    for x in divisors {
+        acc = match pair(&acc, x) {
+            Ok(p) => match quotient(p) {
+                Some(q) => q,
+                None => return arith_error("/", "Divide by zero", a.clone()),
+            },
+            Err(v) => return not_a_number("/", &v),
+        };
+    }
+    Ok(acc)
+}
+
+pub fn cmp(a: &MalVal, b: &MalVal) -> Result<Option<Ordering>, MalVal> {
+    match pair(a, b)? {
+        Pair::Ints(i, j) => Ok(Some(i.cmp(&j))),
+        Pair::Bigs(x, y) => Ok(Some(x.cmp(&y))),
+        Pair::Ratios(x, y) => Ok(Some(x.cmp(&y))),
+        Pair::Floats(x, y) => Ok(x.partial_cmp(&y)),
+    }
+}
+
+// Variadic arithmetic: (op) returns the unit, (op x) combines the unit
+// with x, so (- x) negates, and longer forms fold from the left. The
+// builtins' signatures say which need an argument.
+pub fn fold(unit: i64, f: fn(&MalVal, &MalVal) -> MalRet, a: MalArgs) -> MalRet {
+    match a.len() {
+        0 => Ok(Int(unit)),
+        1 => f(&Int(unit), &a[0]),
+        _ => {
+            let mut acc = a[0].clone();
+            for x in &a[1..] {
+                acc = f(&acc, x)?;
+            }
+            Ok(acc)
+        }
+    }
+}
+
+// Chained comparison: (< a b c) holds when every adjacent pair does.
+// All arguments are checked to be numbers, even once the result is
+// known to be false.
+pub fn chain(op: &str, pred: fn(Ordering) -> bool, a: MalArgs) -> MalRet {
+    match a.len() {
+        1 => match cmp(&a[0], &a[0]) {
+            Ok(_) => Ok(Bool(true)),
+            Err(v) => not_a_number(op, &v),
+        },
+        _ => {
+            let mut res = true;
+            for w in a.windows(2) {
+                match cmp(&w[0], &w[1]) {
+                    Ok(o) => res = res && o.is_some_and(pred),
+                    Err(v) => return not_a_number(op, &v),
+                }
+            }
+            Ok(Bool(res))
+        }
+    }
+}
+
+pub fn int(a: &MalVal) -> MalRet {
+    match a {
+        Int(_) | BigInt(_) => Ok(a.clone()),
+        Ratio(r) => Ok(from_big(r.trunc().to_integer())),
+        Float(f) if f.is_finite() => Ok(from_big(Big::from_f64(f.trunc()).unwrap())),
+        Float(_) => arith_error("int", "Cannot convert non-finite float", vec![a.clone()]),
+        _ => not_a_number("int", a),
+    }
+}
+
+pub fn double(a: &MalVal) -> MalRet {
+    match to_f64(a) {
+        Some(f) => Ok(Float(f)),
+        None => not_a_number("double", a),
+    }
+}
+
+// floor, ceil and round return a Float for a Float argument and an
+// exact integer otherwise: integers are returned unchanged and ratios
+// are rounded exactly.
+pub fn round_with(
+    op: &str,
+    f: fn(f64) -> f64,
+    r: fn(&BigRational) -> BigRational,
+    a: &MalVal,
+) -> MalRet {
+    match a {
+        Int(_) | BigInt(_) => Ok(a.clone()),
+        Ratio(x) => Ok(from_ratio(r(x))),
+        Float(x) => Ok(Float(f(*x))),
+        _ => not_a_number(op, a),
+    }
+}
+
+pub fn sqrt(a: &MalVal) -> MalRet {
+    match to_f64(a) {
+        Some(f) => Ok(Float(f.sqrt())),
+        None => not_a_number("sqrt", a),
+    }
+}
+
+pub fn numerator(a: &MalVal) -> MalRet {
+    match a {
+        Ratio(r) => Ok(from_big(r.numer().clone())),
+        Int(_) | BigInt(_) => Ok(a.clone()),
+        _ => Err(type_error("numerator: expecting a rational number", a)),
+    }
+}
+
+pub fn denominator(a: &MalVal) -> MalRet {
+    match a {
+        Ratio(r) => Ok(from_big(r.denom().clone())),
+        Int(_) | BigInt(_) => Ok(Int(1)),
+        _ => Err(type_error("denominator: expecting a rational number", a)),
+    }
+}
@@ -1,5 +1,10 @@
-use crate::types::MalVal;
-use crate::types::MalVal::{Atom, Bool, Func, Hash, Int, List, MalFunc, Nil, Str, Sym, Vector};
+use std::fmt;
+
+use crate::types::MalVal::{
+    Atom, BigInt, Bool, Float, Func, Hash, Int, Keyword, LazySeq, List, MalFunc, Nil, Ratio, Set,
+    Str, Sym, Vector,
+};
+use crate::types::{format_error, MalVal};
 
 fn escape_str(s: &str) -> String {
     s.chars()
@@ -13,6 +18,18 @@ fn escape_str(s: &str) -> String {
         .join("")
 }
 
+// Debug formatting always includes a '.' or an exponent, so the
+// result reads back as a Float rather than an Int.
+fn pr_float(f: f64) -> String {
+    if f.is_nan() {
+        String::from("##NaN")
+    } else if f.is_infinite() {
+        String::from(if f > 0.0 { "##Inf" } else { "##-Inf" })
+    } else {
+        format!("{:?}", f)
+    }
+}
+
 impl MalVal {
     pub fn pr_str(&self, print_readably: bool) -> String {
         match self {
@@ -20,42 +37,63 @@ impl MalVal {
             Bool(true) => String::from("true"),
             Bool(false) => String::from("false"),
             Int(i) => format!("{}", i),
-            //Float(f)    => format!("{}", f),
+            BigInt(b) => b.to_string(),
+            Ratio(r) => r.to_string(),
+            Float(f) => pr_float(*f),
             Str(s) => {
-                if let Some(keyword) = s.strip_prefix('\u{29e}') {
-                    format!(":{}", keyword)
-                } else if print_readably {
+                if print_readably {
                     format!("\"{}\"", escape_str(s))
                 } else {
                     s.clone()
                 }
             }
+            Keyword(k) => format!(":{}", k),
             Sym(s) => s.clone(),
-            List(l, _) => pr_seq(l, print_readably, "(", ")", " "),
-            Vector(l, _) => pr_seq(l, print_readably, "[", "]", " "),
+            List(l, _) => pr_seq(l.iter(), print_readably, "(", ")", " "),
+            Vector(l, _) => pr_seq(l.iter(), print_readably, "[", "]", " "),
             Hash(hm, _) => {
-                let l: Vec<MalVal> = hm
-                    .iter()
-                    .flat_map(|(k, v)| vec![Str(k.to_string()), v.clone()])
-                    .collect();
-                pr_seq(&l, print_readably, "{", "}", " ")
+                let l: Vec<&MalVal> = hm.iter().flat_map(|(k, v)| vec![k, v]).collect();
+                pr_seq(l, print_readably, "{", "}", " ")
             }
-            Func(_, _) => String::from("#<builtin>"),
+            Set(s, _) => pr_seq(s.iter(), print_readably, "#{", "}", " "),
+            Func(b, _) if b.name.is_empty() => String::from("#<builtin>"),
+            Func(b, _) => format!("#<builtin {}>", b.name),
             MalFunc {
                 ast: a, params: p, ..
-            } => format!("(fn* {} {})", p.pr_str(true), a.pr_str(true)),
+            } => match (&**p, &**a) {
+                (Nil, List(clauses, _)) => pr_seq(clauses.iter(), true, "(fn* ", ")", " "),
+                _ => format!("(fn* {} {})", p.pr_str(true), a.pr_str(true)),
+            },
             Atom(a) => format!("(atom {})", a.borrow().pr_str(true)),
+            // Printing realizes the whole seq. The printer cannot fail, so
+            // an error raised while realizing it is shown in its place.
+            LazySeq(_) => {
+                let strs: Vec<String> = self
+                    .seq_iter()
+                    .map(|x| match x {
+                        Ok(v) => v.pr_str(print_readably),
+                        Err(e) => format!("#<error {}>", format_error(e)),
+                    })
+                    .collect();
+                format!("({})", strs.join(" "))
+            }
         }
     }
 }
 
-pub fn pr_seq(
-    seq: &[MalVal],
+impl fmt::Debug for MalVal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.pr_str(true))
+    }
+}
+
+pub fn pr_seq<'a>(
+    seq: impl IntoIterator<Item = &'a MalVal>,
     print_readably: bool,
     start: &str,
     end: &str,
-    join: &str
+    join: &str,
 ) -> String {
-    let strs: Vec<String> = seq.iter().map(|x| x.pr_str(print_readably)).collect();
+    let strs: Vec<String> = seq.into_iter().map(|x| x.pr_str(print_readably)).collect();
     format!("{}{}{}", start, strs.join(join), end)
 }
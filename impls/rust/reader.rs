@@ -1,14 +1,31 @@
-use regex::{Captures, Regex};
+use std::collections::HashMap;
 use std::rc::Rc;
 
+use num_rational::BigRational;
+use num_traits::Zero;
+use regex::{Captures, Regex};
+
 use crate::types::MalErr::ErrString;
-use crate::types::MalVal::{Bool, Int, List, Nil, Str, Sym, Vector};
-use crate::types::{error, hash_map, MalErr, MalRet, MalVal};
+use crate::types::MalVal::{BigInt, Bool, Float, Int, List, Nil, Str, Sym};
+use crate::types::{
+    error, from_ratio, gensym, hash_map, hash_set, keyword, MalArgs, MalErr, MalRet, MalVal, SrcLoc,
+};
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: usize,
+    column: usize,
+}
 
 #[derive(Debug, Clone)]
 struct Reader {
-    tokens: Vec<String>,
+    tokens: Vec<Token>,
     pos: usize,
+    // the file being read, if any
+    file: Option<Rc<str>>,
+    // inside a syntax-quote, the symbol each foo# in it stands for
+    gensyms: Option<HashMap<String, MalVal>>,
 }
 
 impl Reader {
@@ -18,6 +35,7 @@ impl Reader {
             .tokens
             .get(self.pos - 1)
             .ok_or_else(|| ErrString("underflow".to_string()))?
+            .text
             .to_string())
     }
     fn peek(&self) -> Result<String, MalErr> {
@@ -25,24 +43,52 @@ impl Reader {
             .tokens
             .get(self.pos)
             .ok_or_else(|| ErrString("underflow".to_string()))?
+            .text
             .to_string())
     }
+    // Where the next token is in the file being read.
+    fn loc(&self) -> Option<SrcLoc> {
+        match (&self.file, self.tokens.get(self.pos)) {
+            (Some(file), Some(t)) => Some(SrcLoc {
+                file: file.clone(),
+                line: t.line,
+                column: t.column,
+            }),
+            _ => None,
+        }
+    }
 }
 
-fn tokenize(str: &str) -> Vec<String> {
+fn tokenize(str: &str) -> Vec<Token> {
     lazy_static! {
         static ref RE: Regex = Regex::new(
-            r###"[\s,]*(~@|[\[\]{}()'`~^@]|"(?:\\.|[^\\"])*"?|;.*|[^\s\[\]{}('"`,;)]+)"###
+            r###"[\s,]*(~@|#\{|[\[\]{}()'`~^@]|"(?:\\.|[^\\"])*"?|;.*|[^\s\[\]{}('"`,;)]+)"###
         )
         .unwrap();
     }
 
     let mut res = vec![];
+    // lines and columns count from 1, columns in chars
+    let (mut line, mut column, mut at) = (1, 1, 0);
     for cap in RE.captures_iter(str) {
-        if cap[1].starts_with(';') {
+        let m = cap.get(1).unwrap();
+        for c in str[at..m.start()].chars() {
+            if c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+        at = m.start();
+        if m.as_str().starts_with(';') {
             continue;
         }
-        res.push(String::from(&cap[1]));
+        res.push(Token {
+            text: m.as_str().to_string(),
+            line,
+            column,
+        });
     }
     res
 }
@@ -60,6 +106,11 @@ fn unescape_str(s: &str) -> String {
 fn read_atom(rdr: &mut Reader) -> MalRet {
     lazy_static! {
         static ref INT_RE: Regex = Regex::new(r"^-?[0-9]+$").unwrap();
+        static ref RATIO_RE: Regex = Regex::new(r"^(-?[0-9]+)/([0-9]+)$").unwrap();
+        static ref FLOAT_RE: Regex = Regex::new(
+            r"^-?(?:(?:[0-9]+\.[0-9]*|\.[0-9]+)(?:[eE][-+]?[0-9]+)?|[0-9]+[eE][-+]?[0-9]+)$"
+        )
+        .unwrap();
         static ref STR_RE: Regex = Regex::new(r#""(?:\\.|[^\\"])*""#).unwrap();
     }
     let token = rdr.next()?;
@@ -67,15 +118,42 @@ fn read_atom(rdr: &mut Reader) -> MalRet {
         "nil" => Ok(Nil),
         "false" => Ok(Bool(false)),
         "true" => Ok(Bool(true)),
+        "##Inf" => Ok(Float(f64::INFINITY)),
+        "##-Inf" => Ok(Float(f64::NEG_INFINITY)),
+        "##NaN" => Ok(Float(f64::NAN)),
         _ => {
             if INT_RE.is_match(&token) {
-                Ok(Int(token.parse().unwrap()))
+                match token.parse() {
+                    Ok(i) => Ok(Int(i)),
+                    // too large for an i64
+                    Err(_) => Ok(BigInt(token.parse().unwrap())),
+                }
+            } else if let Some(caps) = RATIO_RE.captures(&token) {
+                let denom: num_bigint::BigInt = caps[2].parse().unwrap();
+                if denom.is_zero() {
+                    return error(&format!("invalid ratio: {}", token));
+                }
+                Ok(from_ratio(BigRational::new(
+                    caps[1].parse().unwrap(),
+                    denom,
+                )))
+            } else if FLOAT_RE.is_match(&token) {
+                Ok(Float(token.parse().unwrap()))
             } else if STR_RE.is_match(&token) {
                 Ok(Str(unescape_str(&token[1..token.len() - 1])))
             } else if token.starts_with('\"') {
                 error("expected '\"', got EOF")
-            } else if let Some(keyword) = token.strip_prefix(':') {
-                Ok(Str(format!("\u{29e}{}", keyword)))
+            } else if let Some(name) = token.strip_prefix(':') {
+                Ok(keyword(name))
+            } else if let (Some(name), Some(gensyms)) = (token.strip_suffix('#'), &mut rdr.gensyms)
+            {
+                if name.is_empty() {
+                    return error("# in syntax-quote must follow a symbol name");
+                }
+                Ok(gensyms
+                    .entry(token.clone())
+                    .or_insert_with(|| gensym(&format!("{}__", name)))
+                    .clone())
             } else {
                 Ok(Sym(token.to_string()))
             }
@@ -83,13 +161,13 @@ fn read_atom(rdr: &mut Reader) -> MalRet {
     }
 }
 
-fn read_seq(rdr: &mut Reader, end: &str) -> MalRet {
+fn read_seq(rdr: &mut Reader, end: &str) -> Result<MalArgs, MalErr> {
     let mut seq: Vec<MalVal> = vec![];
     rdr.next()?;
     loop {
         let token = match rdr.peek() {
             Ok(t) => t,
-            Err(_) => return error(&format!("expected '{}', got EOF", end)),
+            Err(_) => return Err(ErrString(format!("expected '{}', got EOF", end))),
         };
         if token == end {
             break;
@@ -97,12 +175,16 @@ fn read_seq(rdr: &mut Reader, end: &str) -> MalRet {
         seq.push(read_form(rdr)?)
     }
     let _ = rdr.next();
-    match end {
-        ")" => Ok(list!(seq)),
-        "]" => Ok(vector!(seq)),
-        "}" => hash_map(seq),
-        _ => error("read_seq unknown end value"),
-    }
+    Ok(seq)
+}
+
+// foo# symbols are replaced by fresh ones in the syntax-quoted parts of
+// a form, but not in the code unquoted in them.
+fn read_with_gensyms(rdr: &mut Reader, gensyms: Option<HashMap<String, MalVal>>) -> MalRet {
+    let outer = std::mem::replace(&mut rdr.gensyms, gensyms);
+    let form = read_form(rdr);
+    rdr.gensyms = outer;
+    form
 }
 
 fn read_form(rdr: &mut Reader) -> MalRet {
@@ -114,15 +196,18 @@ fn read_form(rdr: &mut Reader) -> MalRet {
         }
         "`" => {
             let _ = rdr.next();
-            Ok(list![Sym("quasiquote".to_string()), read_form(rdr)?])
+            let form = read_with_gensyms(rdr, Some(HashMap::new()))?;
+            Ok(list![Sym("quasiquote".to_string()), form])
         }
         "~" => {
             let _ = rdr.next();
-            Ok(list![Sym("unquote".to_string()), read_form(rdr)?])
+            let form = read_with_gensyms(rdr, None)?;
+            Ok(list![Sym("unquote".to_string()), form])
         }
         "~@" => {
             let _ = rdr.next();
-            Ok(list![Sym("splice-unquote".to_string()), read_form(rdr)?])
+            let form = read_with_gensyms(rdr, None)?;
+            Ok(list![Sym("splice-unquote".to_string()), form])
         }
         "^" => {
             let _ = rdr.next();
@@ -134,11 +219,16 @@ fn read_form(rdr: &mut Reader) -> MalRet {
             Ok(list![Sym("deref".to_string()), read_form(rdr)?])
         }
         ")" => error("unexpected ')'"),
-        "(" => read_seq(rdr, ")"),
+        "(" => {
+            // a list read from a file remembers where it started
+            let meta = rdr.loc().map_or(Nil, |loc| loc.meta());
+            Ok(List(Rc::new(read_seq(rdr, ")")?.into()), Rc::new(meta)))
+        }
         "]" => error("unexpected ']'"),
-        "[" => read_seq(rdr, "]"),
+        "[" => Ok(vector!(read_seq(rdr, "]")?)),
         "}" => error("unexpected '}'"),
-        "{" => read_seq(rdr, "}"),
+        "{" => hash_map(read_seq(rdr, "}")?),
+        "#{" => hash_set(read_seq(rdr, "}")?),
         _ => read_atom(rdr),
     }
 }
@@ -151,6 +241,26 @@ pub fn read_str(str: &str) -> MalRet {
     }
     read_form(&mut Reader {
         pos: 0,
-        tokens
+        tokens,
+        file: None,
+        gensyms: None,
     })
 }
+
+// Read all the forms in the source of a file, into a (do ...) form. The
+// lists read keep their place in the file as metadata, to locate errors
+// raised while evaluating them. Only stepA loads files this way.
+#[allow(dead_code)]
+pub fn read_file(str: &str, file: &str) -> MalRet {
+    let mut rdr = Reader {
+        pos: 0,
+        tokens: tokenize(str),
+        file: Some(file.into()),
+        gensyms: None,
+    };
+    let mut forms = vec![Sym("do".to_string())];
+    while rdr.pos < rdr.tokens.len() {
+        forms.push(read_form(&mut rdr)?);
+    }
+    Ok(list!(forms))
+}
@@ -0,0 +1,574 @@
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::rc::Rc;
+
+use num_traits::ToPrimitive;
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::ser::{self, Serialize};
+
+use crate::types::MalErr::ErrString;
+use crate::types::MalVal::{
+    BigInt, Bool, Float, Hash, Int, Keyword, LazySeq, List, Nil, Ratio, Set, Str, Sym, Vector,
+};
+use crate::types::{keyword, MalArgs, MalErr, MalMap, MalVal};
+
+// Conversion between serde data and mal values, enabled by the "serde"
+// feature. Structs and maps become hash-maps, with struct fields as
+// keyword keys; sequences and tuples become vectors; unit enum variants
+// become keywords and other variants a single entry map from the
+// variant keyword to its data; None and () become nil.
+
+pub fn to_mal<T: Serialize + ?Sized>(value: &T) -> Result<MalVal, MalErr> {
+    value.serialize(Serializer)
+}
+
+pub fn from_mal<T: DeserializeOwned>(value: MalVal) -> Result<T, MalErr> {
+    T::deserialize(value)
+}
+
+impl ser::Error for MalErr {
+    fn custom<T: Display>(msg: T) -> MalErr {
+        ErrString(msg.to_string())
+    }
+}
+
+impl de::Error for MalErr {
+    fn custom<T: Display>(msg: T) -> MalErr {
+        ErrString(msg.to_string())
+    }
+}
+
+fn hash(hm: MalMap) -> MalVal {
+    Hash(Rc::new(hm), Rc::new(Nil))
+}
+
+fn tagged(variant: &str, value: MalVal) -> MalVal {
+    let mut hm = MalMap::default();
+    hm.insert(keyword(variant), value);
+    hash(hm)
+}
+
+struct Serializer;
+
+struct SerializeVec {
+    items: MalArgs,
+    variant: Option<&'static str>,
+}
+
+struct SerializeMap {
+    hm: MalMap,
+    key: Option<MalVal>,
+    variant: Option<&'static str>,
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = MalVal;
+    type Error = MalErr;
+    type SerializeSeq = SerializeVec;
+    type SerializeTuple = SerializeVec;
+    type SerializeTupleStruct = SerializeVec;
+    type SerializeTupleVariant = SerializeVec;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeMap;
+
+    fn serialize_bool(self, v: bool) -> Result<MalVal, MalErr> {
+        Ok(Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<MalVal, MalErr> {
+        Ok(Int(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<MalVal, MalErr> {
+        Ok(Int(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<MalVal, MalErr> {
+        Ok(Int(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<MalVal, MalErr> {
+        Ok(Int(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<MalVal, MalErr> {
+        Ok(Int(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<MalVal, MalErr> {
+        Ok(Int(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<MalVal, MalErr> {
+        Ok(Int(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<MalVal, MalErr> {
+        Ok(match i64::try_from(v) {
+            Ok(i) => Int(i),
+            Err(_) => BigInt(v.into()),
+        })
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<MalVal, MalErr> {
+        Ok(Float(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<MalVal, MalErr> {
+        Ok(Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<MalVal, MalErr> {
+        Ok(Str(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<MalVal, MalErr> {
+        Ok(Str(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<MalVal, MalErr> {
+        Ok(vector!(v
+            .iter()
+            .map(|b| Int((*b).into()))
+            .collect::<MalArgs>()))
+    }
+
+    fn serialize_none(self) -> Result<MalVal, MalErr> {
+        Ok(Nil)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<MalVal, MalErr> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<MalVal, MalErr> {
+        Ok(Nil)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<MalVal, MalErr> {
+        Ok(Nil)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<MalVal, MalErr> {
+        Ok(keyword(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<MalVal, MalErr> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<MalVal, MalErr> {
+        Ok(tagged(variant, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeVec, MalErr> {
+        Ok(SerializeVec {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeVec, MalErr> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeVec, MalErr> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVec, MalErr> {
+        Ok(SerializeVec {
+            items: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMap, MalErr> {
+        Ok(SerializeMap {
+            hm: MalMap::default(),
+            key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeMap, MalErr> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SerializeMap, MalErr> {
+        Ok(SerializeMap {
+            hm: MalMap::default(),
+            key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+impl SerializeVec {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), MalErr> {
+        self.items.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<MalVal, MalErr> {
+        let v = vector!(self.items);
+        Ok(match self.variant {
+            Some(variant) => tagged(variant, v),
+            None => v,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SerializeVec {
+    type Ok = MalVal;
+    type Error = MalErr;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), MalErr> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<MalVal, MalErr> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeVec {
+    type Ok = MalVal;
+    type Error = MalErr;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), MalErr> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<MalVal, MalErr> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeVec {
+    type Ok = MalVal;
+    type Error = MalErr;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), MalErr> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<MalVal, MalErr> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeVec {
+    type Ok = MalVal;
+    type Error = MalErr;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), MalErr> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<MalVal, MalErr> {
+        self.finish()
+    }
+}
+
+impl SerializeMap {
+    fn finish(self) -> Result<MalVal, MalErr> {
+        let hm = hash(self.hm);
+        Ok(match self.variant {
+            Some(variant) => tagged(variant, hm),
+            None => hm,
+        })
+    }
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = MalVal;
+    type Error = MalErr;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), MalErr> {
+        self.key = Some(key.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), MalErr> {
+        match self.key.take() {
+            Some(k) => {
+                self.hm.insert(k, value.serialize(Serializer)?);
+                Ok(())
+            }
+            None => Err(ErrString("map value serialized before its key".to_string())),
+        }
+    }
+
+    fn end(self) -> Result<MalVal, MalErr> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = MalVal;
+    type Error = MalErr;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), MalErr> {
+        self.hm.insert(keyword(key), value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<MalVal, MalErr> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeMap {
+    type Ok = MalVal;
+    type Error = MalErr;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), MalErr> {
+        self.hm.insert(keyword(key), value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<MalVal, MalErr> {
+        self.finish()
+    }
+}
+
+// Deserializing reads a mal value as self-describing data. Keywords and
+// symbols read as strings, so keyword keys match struct field names.
+impl<'de> de::Deserializer<'de> for MalVal {
+    type Error = MalErr;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, MalErr> {
+        match self {
+            Nil => visitor.visit_unit(),
+            Bool(b) => visitor.visit_bool(b),
+            Int(i) => visitor.visit_i64(i),
+            BigInt(ref b) => {
+                if let Some(u) = b.to_u64() {
+                    visitor.visit_u64(u)
+                } else if let Some(i) = b.to_i128() {
+                    visitor.visit_i128(i)
+                } else if let Some(u) = b.to_u128() {
+                    visitor.visit_u128(u)
+                } else {
+                    Err(ErrString(format!("{} is too large", b)))
+                }
+            }
+            Ratio(ref r) => match r.to_f64() {
+                Some(f) => visitor.visit_f64(f),
+                None => Err(ErrString(format!("{} is not representable", r))),
+            },
+            Float(f) => visitor.visit_f64(f),
+            Str(s) | Sym(s) => visitor.visit_string(s),
+            Keyword(k) => visitor.visit_string(k.to_string()),
+            List(_, _) | Vector(_, _) | Set(_, _) | LazySeq(_) => {
+                let items = self.realize_all()?;
+                visitor.visit_seq(SeqDeserializer(items.into_iter()))
+            }
+            Hash(ref hm, _) => visitor.visit_map(MapDeserializer {
+                entries: hm
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+                value: None,
+            }),
+            _ => Err(ErrString(format!(
+                "cannot deserialize {}",
+                self.pr_str(true)
+            ))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, MalErr> {
+        match self {
+            Nil => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, MalErr> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, MalErr> {
+        match self {
+            Keyword(_) | Str(_) => visitor.visit_enum(EnumDeserializer {
+                variant: self,
+                value: None,
+            }),
+            Hash(ref hm, _) if hm.len() == 1 => {
+                let (k, v) = hm.iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer {
+                    variant: k.clone(),
+                    value: Some(v.clone()),
+                })
+            }
+            _ => Err(ErrString(format!(
+                "expected an enum variant, got {}",
+                self.pr_str(true)
+            ))),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, MalErr> for MalVal {
+    type Deserializer = MalVal;
+
+    fn into_deserializer(self) -> MalVal {
+        self
+    }
+}
+
+struct SeqDeserializer<I>(I);
+
+impl<'de, I: Iterator<Item = MalVal>> SeqAccess<'de> for SeqDeserializer<I> {
+    type Error = MalErr;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, MalErr> {
+        match self.0.next() {
+            Some(v) => seed.deserialize(v).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+struct MapDeserializer<I> {
+    entries: I,
+    value: Option<MalVal>,
+}
+
+impl<'de, I: Iterator<Item = (MalVal, MalVal)>> MapAccess<'de> for MapDeserializer<I> {
+    type Error = MalErr;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, MalErr> {
+        match self.entries.next() {
+            Some((k, v)) => {
+                self.value = Some(v);
+                seed.deserialize(k).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, MalErr> {
+        match self.value.take() {
+            Some(v) => seed.deserialize(v),
+            None => Err(ErrString("map value requested before its key".to_string())),
+        }
+    }
+}
+
+struct EnumDeserializer {
+    variant: MalVal,
+    value: Option<MalVal>,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+    type Error = MalErr;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, VariantDeserializer), MalErr> {
+        let variant = seed.deserialize(self.variant)?;
+        Ok((variant, VariantDeserializer(self.value)))
+    }
+}
+
+struct VariantDeserializer(Option<MalVal>);
+
+impl<'de> VariantAccess<'de> for VariantDeserializer {
+    type Error = MalErr;
+
+    fn unit_variant(self) -> Result<(), MalErr> {
+        match self.0 {
+            None | Some(Nil) => Ok(()),
+            Some(v) => Err(ErrString(format!(
+                "expected a unit variant, got {}",
+                v.pr_str(true)
+            ))),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, MalErr> {
+        seed.deserialize(self.0.unwrap_or(Nil))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, MalErr> {
+        de::Deserializer::deserialize_seq(self.0.unwrap_or(Nil), visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, MalErr> {
+        de::Deserializer::deserialize_map(self.0.unwrap_or(Nil), visitor)
+    }
+}
@@ -1,11 +1,14 @@
-use std::rc::Rc;
 //use std::collections::HashMap;
 use fnv::FnvHashMap;
 
 #[macro_use]
 extern crate lazy_static;
 extern crate fnv;
+extern crate im_rc;
 extern crate itertools;
+extern crate num_bigint;
+extern crate num_rational;
+extern crate num_traits;
 extern crate regex;
 
 extern crate rustyline;
@@ -16,8 +19,8 @@ use rustyline::Editor;
 #[allow(dead_code)]
 mod types;
 use crate::types::MalErr::ErrString;
-use crate::types::MalVal::{Hash, Int, List, Nil, Sym, Vector};
-use crate::types::{error, format_error, func, MalArgs, MalErr, MalRet, MalVal};
+use crate::types::MalVal::{Hash, Int, List, Sym, Vector};
+use crate::types::{error, format_error, func, hash_map, MalArgs, MalErr, MalRet, MalVal};
 mod printer;
 mod reader;
 // TODO: figure out a way to avoid including env
@@ -47,11 +50,12 @@ fn eval(ast: &MalVal, env: &Env) -> MalRet {
             Ok(vector!(lst))
         }
         Hash(hm, _) => {
-            let mut new_hm: FnvHashMap<String, MalVal> = FnvHashMap::default();
+            let mut kvs: MalArgs = vec![];
             for (k, v) in hm.iter() {
-                new_hm.insert(k.to_string(), eval(v, env)?);
+                kvs.push(eval(k, env)?);
+                kvs.push(eval(v, env)?);
             }
-            Ok(Hash(Rc::new(new_hm), Rc::new(Nil)))
+            hash_map(kvs)
         }
         List(l, _) => {
             if l.is_empty() {
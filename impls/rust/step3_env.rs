@@ -1,12 +1,14 @@
-use std::rc::Rc;
 //use std::collections::HashMap;
-use fnv::FnvHashMap;
 use itertools::Itertools;
 
 #[macro_use]
 extern crate lazy_static;
 extern crate fnv;
+extern crate im_rc;
 extern crate itertools;
+extern crate num_bigint;
+extern crate num_rational;
+extern crate num_traits;
 extern crate regex;
 
 extern crate rustyline;
@@ -17,7 +19,8 @@ use rustyline::Editor;
 #[allow(dead_code)]
 mod types;
 use crate::types::MalVal::{Bool, Hash, Int, List, Nil, Sym, Vector};
-use crate::types::{error, format_error, func, MalArgs, MalErr, MalRet, MalVal};
+use crate::types::{error, format_error, func, hash_map, MalArgs, MalErr, MalRet, MalVal};
+#[allow(dead_code)]
 mod env;
 mod printer;
 mod reader;
@@ -37,8 +40,8 @@ fn eval(ast: &MalVal, env: &Env) -> MalRet {
     match ast {
         Sym(s) => match env_get(env, s) {
             Some(r) => Ok(r),
-            None => error (&format!("'{}' not found", s)),
-        }
+            None => error(&format!("'{}' not found", s)),
+        },
         Vector(v, _) => {
             let mut lst: MalArgs = vec![];
             for a in v.iter() {
@@ -47,11 +50,12 @@ fn eval(ast: &MalVal, env: &Env) -> MalRet {
             Ok(vector!(lst))
         }
         Hash(hm, _) => {
-            let mut new_hm: FnvHashMap<String, MalVal> = FnvHashMap::default();
+            let mut kvs: MalArgs = vec![];
             for (k, v) in hm.iter() {
-                new_hm.insert(k.to_string(), eval(v, env)?);
+                kvs.push(eval(k, env)?);
+                kvs.push(eval(v, env)?);
             }
-            Ok(Hash(Rc::new(new_hm), Rc::new(Nil)))
+            hash_map(kvs)
         }
         List(l, _) => {
             if l.is_empty() {
@@ -59,9 +63,7 @@ fn eval(ast: &MalVal, env: &Env) -> MalRet {
             }
             let a0 = &l[0];
             match a0 {
-                Sym(a0sym) if a0sym == "def!" => {
-                    env_set(env, &l[1], eval(&l[2], env)?)
-                }
+                Sym(a0sym) if a0sym == "def!" => env_set(env, &l[1], eval(&l[2], env)?),
                 Sym(a0sym) if a0sym == "let*" => {
                     let let_env = &env_new(Some(env.clone()));
                     let (a1, a2) = (&l[1], &l[2]);
@@ -85,7 +87,7 @@ fn eval(ast: &MalVal, env: &Env) -> MalRet {
                         args.push(eval(&l[i], env)?);
                     }
                     f.apply(args)
-                },
+                }
             }
         }
         _ => Ok(ast.clone()),
@@ -1,12 +1,15 @@
 use std::rc::Rc;
 //use std::collections::HashMap;
-use fnv::FnvHashMap;
 use itertools::Itertools;
 
 #[macro_use]
 extern crate lazy_static;
 extern crate fnv;
+extern crate im_rc;
 extern crate itertools;
+extern crate num_bigint;
+extern crate num_rational;
+extern crate num_traits;
 extern crate regex;
 
 extern crate rustyline;
@@ -16,13 +19,16 @@ use rustyline::Editor;
 #[macro_use]
 mod types;
 use crate::types::MalVal::{Bool, Hash, List, MalFunc, Nil, Sym, Vector};
-use crate::types::{error, format_error, MalArgs, MalErr, MalRet, MalVal};
+use crate::types::{error, format_error, hash_map, MalArgs, MalErr, MalRet, MalVal};
+#[allow(dead_code)]
 mod env;
 mod printer;
 mod reader;
 use crate::env::{env_get, env_new, env_set, env_sets, Env};
 #[macro_use]
 mod core;
+mod lazy;
+mod numeric;
 
 // read
 fn read(str: &str) -> MalRet {
@@ -38,8 +44,8 @@ fn eval(ast: &MalVal, env: &Env) -> MalRet {
     match ast {
         Sym(s) => match env_get(env, s) {
             Some(r) => Ok(r),
-            None => error (&format!("'{}' not found", s)),
-        }
+            None => error(&format!("'{}' not found", s)),
+        },
         Vector(v, _) => {
             let mut lst: MalArgs = vec![];
             for a in v.iter() {
@@ -48,11 +54,12 @@ fn eval(ast: &MalVal, env: &Env) -> MalRet {
             Ok(vector!(lst))
         }
         Hash(hm, _) => {
-            let mut new_hm: FnvHashMap<String, MalVal> = FnvHashMap::default();
+            let mut kvs: MalArgs = vec![];
             for (k, v) in hm.iter() {
-                new_hm.insert(k.to_string(), eval(v, env)?);
+                kvs.push(eval(k, env)?);
+                kvs.push(eval(v, env)?);
             }
-            Ok(Hash(Rc::new(new_hm), Rc::new(Nil)))
+            hash_map(kvs)
         }
         List(l, _) => {
             if l.is_empty() {
@@ -60,9 +67,7 @@ fn eval(ast: &MalVal, env: &Env) -> MalRet {
             }
             let a0 = &l[0];
             match a0 {
-                Sym(a0sym) if a0sym == "def!" => {
-                    env_set(env, &l[1], eval(&l[2], env)?)
-                }
+                Sym(a0sym) if a0sym == "def!" => env_set(env, &l[1], eval(&l[2], env)?),
                 Sym(a0sym) if a0sym == "let*" => {
                     let let_env = &env_new(Some(env.clone()));
                     let (a1, a2) = (&l[1], &l[2]);
@@ -102,6 +107,7 @@ fn eval(ast: &MalVal, env: &Env) -> MalRet {
                         env: env.clone(),
                         params: Rc::new(a1),
                         is_macro: false,
+                        name: None,
                         meta: Rc::new(Nil),
                     })
                 }
@@ -112,7 +118,7 @@ fn eval(ast: &MalVal, env: &Env) -> MalRet {
                         args.push(eval(&l[i], env)?);
                     }
                     f.apply(args)
-                },
+                }
             }
         }
         _ => Ok(ast.clone()),
@@ -1,12 +1,15 @@
 use std::rc::Rc;
 //use std::collections::HashMap;
-use fnv::FnvHashMap;
 use itertools::Itertools;
 
 #[macro_use]
 extern crate lazy_static;
 extern crate fnv;
+extern crate im_rc;
 extern crate itertools;
+extern crate num_bigint;
+extern crate num_rational;
+extern crate num_traits;
 extern crate regex;
 
 extern crate rustyline;
@@ -16,13 +19,15 @@ use rustyline::Editor;
 #[macro_use]
 mod types;
 use crate::types::MalVal::{Bool, Func, Hash, List, MalFunc, Nil, Str, Sym, Vector};
-use crate::types::{error, format_error, MalArgs, MalErr, MalRet, MalVal};
+use crate::types::{error, format_error, hash_map, MalArgs, MalErr, MalRet, MalVal};
 mod env;
 mod printer;
 mod reader;
 use crate::env::{env_bind, env_find_repl, env_get, env_new, env_set, env_sets, Env};
 #[macro_use]
 mod core;
+mod lazy;
+mod numeric;
 
 // read
 fn read(str: &str) -> MalRet {
@@ -44,25 +49,26 @@ fn eval(orig_ast: &MalVal, orig_env: &Env) -> MalRet {
             _ => println!("EVAL: {}", print(ast)),
         }
         match ast {
-        Sym(s) => match env_get(env, s) {
-            Some(r) => return Ok(r),
-            None => return error(&format!("'{}' not found", s)),
-        }
-        Vector(v, _) => {
-            let mut lst: MalArgs = vec![];
-            for a in v.iter() {
-                lst.push(eval(a, env)?);
+            Sym(s) => match env_get(env, s) {
+                Some(r) => return Ok(r),
+                None => return error(&format!("'{}' not found", s)),
+            },
+            Vector(v, _) => {
+                let mut lst: MalArgs = vec![];
+                for a in v.iter() {
+                    lst.push(eval(a, env)?);
+                }
+                return Ok(vector!(lst));
             }
-            return Ok(vector!(lst));
-        }
-        Hash(hm, _) => {
-            let mut new_hm: FnvHashMap<String, MalVal> = FnvHashMap::default();
-            for (k, v) in hm.iter() {
-                new_hm.insert(k.to_string(), eval(v, env)?);
+            Hash(hm, _) => {
+                let mut kvs: MalArgs = vec![];
+                for (k, v) in hm.iter() {
+                    kvs.push(eval(k, env)?);
+                    kvs.push(eval(v, env)?);
+                }
+                return hash_map(kvs);
             }
-            return Ok(Hash(Rc::new(new_hm), Rc::new(Nil)));
-        }
-        List(l, _) => {
+            List(l, _) => {
                 if l.is_empty() {
                     return Ok(ast.clone());
                 }
@@ -123,8 +129,9 @@ fn eval(orig_ast: &MalVal, orig_env: &Env) -> MalRet {
                             env: env.clone(),
                             params: Rc::new(a1),
                             is_macro: false,
+                            name: None,
                             meta: Rc::new(Nil),
-                        })
+                        });
                     }
                     Sym(a0sym) if a0sym == "eval" => {
                         //  Hard to implement without global variables.
@@ -136,39 +143,43 @@ fn eval(orig_ast: &MalVal, orig_env: &Env) -> MalRet {
                         continue 'tco;
                     }
                     _ => match eval(a0, env) {
-                                Ok(f @ Func(_, _)) => {
-                                    let mut args: MalArgs = vec![];
-                                    for i in 1..l.len() {
-                                        args.push(eval(&l[i], env)?);
-                                    }
-                                    return f.apply(args);
-                                }
-                                Ok(MalFunc {
-                                    ast: mast,
-                                    env: menv,
-                                    params: mparams,
-                                    ..
-                                }) => {
-                                    let mut args: MalArgs = vec![];
-                                    for i in 1..l.len() {
-                                        args.push(eval(&l[i], env)?);
-                                    }
-                                    live_env = env_bind(Some(menv.clone()), &mparams, args.to_vec())?;
-                                    env = &live_env;
-                                    live_ast = (*mast).clone();
-                                    ast = &live_ast;
-                                    continue 'tco;
-                                }
-                                Ok(_) => return error("attempt to call non-function"),
-                                e @ Err(_) => return e,
+                        Ok(f @ Func(_, _)) => {
+                            let mut args: MalArgs = vec![];
+                            for i in 1..l.len() {
+                                args.push(eval(&l[i], env)?);
+                            }
+                            return f.apply(args);
+                        }
+                        Ok(MalFunc {
+                            ast: mast,
+                            env: menv,
+                            params: mparams,
+                            name: mname,
+                            ..
+                        }) => {
+                            let mut args: MalArgs = vec![];
+                            for i in 1..l.len() {
+                                args.push(eval(&l[i], env)?);
+                            }
+                            live_env = env_bind(
+                                Some(menv.clone()),
+                                &mparams,
+                                args.to_vec(),
+                                mname.as_deref(),
+                            )?;
+                            env = &live_env;
+                            live_ast = (*mast).clone();
+                            ast = &live_ast;
+                            continue 'tco;
+                        }
+                        Ok(_) => return error("attempt to call non-function"),
+                        e @ Err(_) => return e,
                     },
                 }
-        }
-        _ => return Ok(ast.clone()),
+            }
+            _ => return Ok(ast.clone()),
         };
-
     } // end 'tco loop
-
 }
 
 // print
@@ -206,7 +217,11 @@ fn main() {
     for (k, v) in core::ns() {
         env_sets(&repl_env, k, v);
     }
-    env_sets(&repl_env, "*ARGV*", list!(args.map(Str).collect()));
+    env_sets(
+        &repl_env,
+        "*ARGV*",
+        list!(args.map(Str).collect::<Vec<MalVal>>()),
+    );
 
     // core.mal: defined using the language itself
     re("(def! not (fn* (a) (if a false true)))", &repl_env);
@@ -0,0 +1,37 @@
+// The From and TryFrom conversions between Rust and mal values.
+
+extern crate mal;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use mal::reader::read_str;
+use mal::types::MalVal;
+
+fn read(src: &str) -> MalVal {
+    read_str(src).unwrap()
+}
+
+#[test]
+fn conversions() {
+    let v = MalVal::from(vec![Some(1i64), None]);
+    assert_eq!(v, read("[1 nil]"));
+    assert_eq!(
+        Vec::<i64>::try_from(read("(1 2 3)")).unwrap(),
+        vec![1, 2, 3]
+    );
+    assert_eq!(Vec::<i64>::try_from(read("#{7}")).unwrap(), vec![7]);
+    assert_eq!(f64::try_from(read("3/2")).unwrap(), 1.5);
+    assert_eq!(String::try_from(read(":kw")).unwrap(), "kw");
+
+    let mut m = HashMap::new();
+    m.insert("a".to_string(), 1i64);
+    let v = MalVal::from(m.clone());
+    assert_eq!(v, read(r#"{"a" 1}"#));
+    assert_eq!(HashMap::<String, i64>::try_from(read("{:a 1}")).unwrap(), m);
+
+    let err = i64::try_from(read("1/2")).unwrap_err();
+    assert_eq!(err.to_string(), "expected an integer, got 1/2");
+    assert!(bool::try_from(read("nil")).is_err());
+    assert!(HashMap::<i64, i64>::try_from(read("[1 2]")).is_err());
+}
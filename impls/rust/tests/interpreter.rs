@@ -0,0 +1,116 @@
+// Embedding the interpreter: evaluating, defining and calling from Rust.
+
+extern crate mal;
+
+use std::cell::Cell;
+use std::convert::TryFrom;
+use std::fs;
+use std::rc::Rc;
+
+use mal::env::env_get;
+use mal::reader::read_str;
+use mal::types::MalVal::{Int, Str};
+use mal::types::{builtin, Arity, MalVal};
+use mal::Interpreter;
+
+fn read(src: &str) -> MalVal {
+    read_str(src).unwrap()
+}
+
+#[test]
+fn eval_str() {
+    let mal = Interpreter::new();
+    assert_eq!(mal.eval_str("(+ 1 2)").unwrap(), Int(3));
+    assert_eq!(mal.eval_str("(def! x 5) (* x x)").unwrap(), Int(25));
+    assert_eq!(mal.eval_str("").unwrap(), read("nil"));
+    assert_eq!(mal.eval(&read("(list x :k)")).unwrap(), read("(5 :k)"));
+    assert_eq!(mal.get("x"), Some(Int(5)));
+    assert_eq!(mal.get("no-such-name"), None);
+}
+
+#[test]
+fn define_and_call() {
+    let mal = Interpreter::new();
+    mal.define("greeting", Str("hello".to_string()));
+    let f = mal.eval_str("(fn* [x] (str greeting \" \" x))").unwrap();
+    let s = mal.call(&f, vec![Str("world".to_string())]).unwrap();
+    assert_eq!(s, Str("hello world".to_string()));
+
+    let plus = mal.get("+").unwrap();
+    assert_eq!(mal.call(&plus, vec![Int(1), Int(2)]).unwrap(), Int(3));
+    let err = mal.call(&f, vec![]).unwrap_err().into_value();
+    assert_eq!(err.ex_data(), Some(read("{:type :arity :fn nil :got 0}")));
+}
+
+#[test]
+fn env_holds_definitions() {
+    let mal = Interpreter::new();
+    mal.eval_str("(def! y 7)").unwrap();
+    assert_eq!(env_get(mal.env(), "y"), Some(Int(7)));
+    mal.set_argv(vec!["a".to_string(), "b".to_string()]);
+    assert_eq!(mal.eval_str("*ARGV*").unwrap(), read(r#"("a" "b")"#));
+}
+
+#[test]
+fn errors() {
+    let mal = Interpreter::new();
+    let err = mal
+        .eval_str("(throw (ex-info \"boom\" {:n 1}))")
+        .unwrap_err();
+    let v = err.into_value();
+    assert_eq!(v.ex_message(), Some("boom".to_string()));
+    assert_eq!(v.ex_data(), Some(read("{:n 1}")));
+
+    let err = mal.eval_str("no-such-sym").unwrap_err();
+    assert_eq!(err.to_string(), "'no-such-sym' not found");
+    assert!(mal.eval_str("(+ 1").is_err());
+}
+
+#[test]
+fn eval_file() {
+    let dir = std::env::temp_dir();
+    let ok = dir.join(format!("mal-interpreter-ok-{}.mal", std::process::id()));
+    let bad = dir.join(format!("mal-interpreter-bad-{}.mal", std::process::id()));
+    fs::write(&ok, "(def! z 3)\n(+ z 1)\n").unwrap();
+    fs::write(&bad, "(def! w 1)\n\n(nth [] w)\n").unwrap();
+
+    let mal = Interpreter::new();
+    assert_eq!(mal.eval_file(&ok).unwrap(), Int(4));
+    assert_eq!(mal.get("z"), Some(Int(3)));
+
+    // the error says where in the file it was raised
+    let err = mal.eval_file(&bad).unwrap_err().to_string();
+    let at = format!("{}:3:", bad.display());
+    assert!(err.starts_with(&at), "{}", err);
+    assert!(err.contains("nth: index out of range"), "{}", err);
+
+    let missing = dir.join("mal-interpreter-no-such-file.mal");
+    assert!(mal.eval_file(&missing).is_err());
+
+    fs::remove_file(ok).unwrap();
+    fs::remove_file(bad).unwrap();
+}
+
+#[test]
+fn builtin_closures() {
+    let mal = Interpreter::new();
+    let count = Rc::new(Cell::new(0));
+    let counted = count.clone();
+    let counter = builtin("next!", Some(Arity::exactly(1)), move |a| {
+        counted.set(counted.get() + i64::try_from(a[0].clone())?);
+        Ok(Int(counted.get()))
+    });
+    mal.define("next!", counter);
+    assert_eq!(mal.eval_str("(next! 2) (next! 3)").unwrap(), Int(5));
+    assert_eq!(mal.eval_str("(map next! [1 1])").unwrap(), read("(6 7)"));
+    assert_eq!(count.get(), 7);
+
+    let err = mal.eval_str("(next!)").unwrap_err().into_value();
+    assert_eq!(
+        err.ex_data(),
+        Some(read(r#"{:type :arity :fn "next!" :got 0}"#))
+    );
+    let err = mal.eval_str("(next! 1 2)").unwrap_err().to_string();
+    assert!(err.contains("next!"), "{}", err);
+    assert_eq!(count.get(), 7);
+}
@@ -0,0 +1,161 @@
+// Round trips between Rust data and mal values through serde.
+#![cfg(feature = "serde")]
+
+extern crate mal;
+#[macro_use]
+extern crate serde;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use mal::reader::read_str;
+use mal::types::MalVal;
+use mal::{from_mal, to_mal, Interpreter};
+
+fn read(src: &str) -> MalVal {
+    read_str(src).unwrap()
+}
+
+#[test]
+fn maps_with_non_string_keys() {
+    let mut m = HashMap::new();
+    m.insert(1i64, "one".to_string());
+    m.insert(-2, "minus two".to_string());
+    let v = to_mal(&m).unwrap();
+    assert_eq!(v, read(r#"{1 "one" -2 "minus two"}"#));
+    assert_eq!(from_mal::<HashMap<i64, String>>(v).unwrap(), m);
+
+    let m: BTreeMap<Vec<i64>, bool> = from_mal(read("{[1 2] true [] false}")).unwrap();
+    assert_eq!(m.get(&vec![1, 2]), Some(&true));
+    assert_eq!(m.get(&vec![]), Some(&false));
+    assert_eq!(to_mal(&m).unwrap(), read("{[] false [1 2] true}"));
+}
+
+#[test]
+fn sets() {
+    let s: BTreeSet<i64> = from_mal(read("#{3 1 2}")).unwrap();
+    assert_eq!(s, [1, 2, 3].iter().cloned().collect());
+    // sets serialize as vectors, in the set's order
+    assert_eq!(to_mal(&s).unwrap(), read("[1 2 3]"));
+    let back: BTreeSet<String> = from_mal(read(r#"#{"a" :b}"#)).unwrap();
+    assert_eq!(back, ["a", "b"].iter().map(|s| s.to_string()).collect());
+}
+
+#[test]
+fn ratios() {
+    assert_eq!(from_mal::<f64>(read("1/2")).unwrap(), 0.5);
+    assert_eq!(
+        from_mal::<Vec<f64>>(read("[-3/4 2]")).unwrap(),
+        vec![-0.75, 2.0]
+    );
+    assert!(from_mal::<i64>(read("1/3")).is_err());
+}
+
+#[test]
+fn bigints() {
+    let v = to_mal(&u64::MAX).unwrap();
+    assert_eq!(v, read("18446744073709551615"));
+    assert_eq!(from_mal::<u64>(v).unwrap(), u64::MAX);
+    assert_eq!(
+        from_mal::<i64>(read("-9223372036854775808")).unwrap(),
+        i64::MIN
+    );
+    assert_eq!(
+        from_mal::<i128>(read("-9223372036854775809")).unwrap(),
+        i128::from(i64::MIN) - 1
+    );
+    assert!(from_mal::<i64>(read("9223372036854775808")).is_err());
+    assert!(from_mal::<u128>(read("999999999999999999999999999999999999999999")).is_err());
+}
+
+#[test]
+fn nested_round_trip() {
+    let data: Vec<(String, Option<Vec<bool>>)> = vec![
+        ("some".to_string(), Some(vec![true, false])),
+        ("none".to_string(), None),
+    ];
+    let v = to_mal(&data).unwrap();
+    assert_eq!(v, read(r#"[["some" [true false]] ["none" nil]]"#));
+    assert_eq!(
+        from_mal::<Vec<(String, Option<Vec<bool>>)>>(v).unwrap(),
+        data
+    );
+}
+
+#[test]
+fn functions_and_atoms_are_rejected() {
+    let mal = Interpreter::new();
+    for src in &["(fn* [x] x)", "+", "(atom 1)", "[1 (atom 2)]", "{:f not}"] {
+        let v = mal.eval_str(src).unwrap();
+        let err = from_mal::<serde::de::IgnoredAny>(v).unwrap_err();
+        assert!(
+            err.to_string().starts_with("cannot deserialize"),
+            "{}: {}",
+            src,
+            err
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Meters(f64);
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Shape {
+    Point,
+    Circle(Meters),
+    Rect { width: Meters, height: Meters },
+    Poly(Vec<(i64, i64)>, bool),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Drawing {
+    name: String,
+    shapes: Vec<Shape>,
+    scale: Option<Meters>,
+    layer: Option<u8>,
+}
+
+#[test]
+fn derived_structs_and_enums() {
+    let d = Drawing {
+        name: "d".to_string(),
+        shapes: vec![
+            Shape::Point,
+            Shape::Circle(Meters(1.5)),
+            Shape::Rect {
+                width: Meters(2.0),
+                height: Meters(0.5),
+            },
+            Shape::Poly(vec![(0, 0), (1, 2)], true),
+        ],
+        scale: Some(Meters(10.0)),
+        layer: None,
+    };
+    // fields are keyword keys, newtypes are their contents, unit variants
+    // are keywords and other variants a map from the variant keyword
+    let v = to_mal(&d).unwrap();
+    let expected = read(
+        r#"{:name "d"
+            :shapes [:Point
+                     {:Circle 1.5}
+                     {:Rect {:width 2.0 :height 0.5}}
+                     {:Poly [[[0 0] [1 2]] true]}]
+            :scale 10.0
+            :layer nil}"#,
+    );
+    assert_eq!(v, expected);
+    assert_eq!(from_mal::<Drawing>(v).unwrap(), d);
+
+    // written in mal, missing options are None
+    let mal = Interpreter::new();
+    let v = mal
+        .eval_str(r#"{:name (str "a" "b") :shapes [:Point {:Circle (/ 1 2)}] :scale nil}"#)
+        .unwrap();
+    let d: Drawing = from_mal(v).unwrap();
+    assert_eq!(d.name, "ab");
+    assert_eq!(d.shapes, vec![Shape::Point, Shape::Circle(Meters(0.5))]);
+    assert_eq!((d.scale, d.layer), (None, None));
+
+    assert!(from_mal::<Shape>(read(":Square")).is_err());
+    assert!(from_mal::<Drawing>(read("{:name 1 :shapes []}")).is_err());
+}
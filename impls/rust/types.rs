@@ -1,63 +1,411 @@
+use fnv::{FnvBuildHasher, FnvHashMap, FnvHasher};
+use itertools::Itertools;
+use num_rational::BigRational;
+use num_traits::{One, ToPrimitive};
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
 use std::rc::Rc;
-//use std::collections::HashMap;
-use fnv::FnvHashMap;
-use itertools::Itertools;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::env::{env_bind, Env};
-use crate::types::MalErr::{ErrMalVal, ErrString};
-use crate::types::MalVal::{Atom, Bool, Func, Hash, Int, List, MalFunc, Nil, Str, Sym, Vector};
+use crate::types::MalErr::{ErrEx, ErrMalVal, ErrString, ErrTrace};
+use crate::types::MalVal::{
+    Atom, BigInt, Bool, Float, Func, Hash, Int, Keyword, LazySeq, List, MalFunc, Nil, Ratio, Set,
+    Str, Sym, Vector,
+};
 
 #[derive(Clone)]
 pub enum MalVal {
     Nil,
     Bool(bool),
     Int(i64),
-    //Float(f64),
+    BigInt(num_bigint::BigInt),
+    Ratio(BigRational),
+    Float(f64),
     Str(String),
+    Keyword(Rc<str>),
     Sym(String),
-    List(Rc<Vec<MalVal>>, Rc<MalVal>),
-    Vector(Rc<Vec<MalVal>>, Rc<MalVal>),
-    Hash(Rc<FnvHashMap<String, MalVal>>, Rc<MalVal>),
-    Func(fn(MalArgs) -> MalRet, Rc<MalVal>),
+    List(Rc<MalSeq>, Rc<MalVal>),
+    Vector(Rc<MalSeq>, Rc<MalVal>),
+    Hash(Rc<MalMap>, Rc<MalVal>),
+    Set(Rc<MalSet>, Rc<MalVal>),
+    Func(Rc<Builtin>, Rc<MalVal>),
     MalFunc {
         eval: fn(ast: &MalVal, env: &Env) -> MalRet,
         ast: Rc<MalVal>,
         env: Env,
         params: Rc<MalVal>,
         is_macro: bool,
+        name: Option<Rc<str>>,
         meta: Rc<MalVal>,
     },
     Atom(Rc<RefCell<MalVal>>),
+    LazySeq(Rc<RefCell<Lazy>>),
+}
+
+// A lazy seq cell is either still pending, as a function and the
+// arguments to call it with, or realized into its first element and the
+// rest of the seq (None when empty). The rest is usually another lazy
+// seq, so long and infinite seqs are realized one cell at a time.
+pub enum Lazy {
+    Pending(MalVal, MalArgs),
+    Realized(Option<(MalVal, MalVal)>),
+}
+
+// A function implemented in Rust. The closure may capture state, so a
+// host program can expose its own functions to mal. The name shows up in
+// error messages and when printing, and a known arity and the argument
+// types are checked before the closure is called.
+#[derive(Clone)]
+pub struct Builtin {
+    pub name: String,
+    pub arity: Option<Arity>,
+    // The types of the leading arguments; the last one also applies to
+    // any further arguments. Empty means anything goes.
+    pub types: Vec<ArgType>,
+    pub f: Rc<dyn Fn(MalArgs) -> MalRet>,
+}
+
+impl Builtin {
+    pub fn check_args(&self, args: &MalArgs) -> Result<(), MalErr> {
+        if let Some(arity) = self.arity {
+            if !arity.accepts(args.len()) {
+                let msg = format!(
+                    "wrong number of args to {} (got {}, expected {})",
+                    self.name,
+                    args.len(),
+                    arity
+                );
+                return Err(ex_error(
+                    "arity",
+                    &msg,
+                    vec![
+                        ("fn", Str(self.name.clone())),
+                        ("got", Int(args.len() as i64)),
+                    ],
+                ));
+            }
+        }
+        for (i, a) in args.iter().enumerate() {
+            match self.types.get(i).or(self.types.last()) {
+                Some(t) if !t.accepts(a) => {
+                    let msg = format!(
+                        "wrong type of arg {} to {} (got {}, expected {})",
+                        i + 1,
+                        self.name,
+                        a.pr_str(true),
+                        t
+                    );
+                    return Err(ex_error(
+                        "wrong-type",
+                        &msg,
+                        vec![
+                            ("fn", Str(self.name.clone())),
+                            ("arg", Int(i as i64 + 1)),
+                            ("got", a.clone()),
+                        ],
+                    ));
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+// The number of arguments a builtin accepts; no max means variadic.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Arity {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+// Not every step binary builds builtins with a known arity.
+#[allow(dead_code)]
+impl Arity {
+    pub fn exactly(n: usize) -> Arity {
+        Arity {
+            min: n,
+            max: Some(n),
+        }
+    }
+
+    pub fn at_least(n: usize) -> Arity {
+        Arity { min: n, max: None }
+    }
+
+    pub fn between(min: usize, max: usize) -> Arity {
+        Arity {
+            min,
+            max: Some(max),
+        }
+    }
+
+    pub fn accepts(&self, n: usize) -> bool {
+        n >= self.min && self.max.is_none_or(|max| n <= max)
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{}", max),
+            Some(max) => write!(f, "{} to {}", self.min, max),
+            None => write!(f, "at least {}", self.min),
+        }
+    }
+}
+
+// The type of a builtin's argument.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArgType {
+    Any,
+    Num,
+    Int,
+    Str,
+    // anything first and rest work on, including nil
+    Seq,
+    Map,
+    Set,
+    Fn,
+    Atom,
 }
 
+impl ArgType {
+    pub fn accepts(&self, v: &MalVal) -> bool {
+        match self {
+            ArgType::Any => true,
+            ArgType::Num => matches!(v, Int(_) | BigInt(_) | Ratio(_) | Float(_)),
+            ArgType::Int => matches!(v, Int(_)),
+            ArgType::Str => matches!(v, Str(_)),
+            ArgType::Seq => matches!(v, List(..) | Vector(..) | Set(..) | LazySeq(_) | Nil),
+            ArgType::Map => matches!(v, Hash(..)),
+            ArgType::Set => matches!(v, Set(..)),
+            ArgType::Fn => matches!(v, Func(..) | MalFunc { .. }),
+            ArgType::Atom => matches!(v, Atom(_)),
+        }
+    }
+}
+
+impl fmt::Display for ArgType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ArgType::Any => "anything",
+            ArgType::Num => "a number",
+            ArgType::Int => "an integer",
+            ArgType::Str => "a string",
+            ArgType::Seq => "a seq",
+            ArgType::Map => "a hash-map",
+            ArgType::Set => "a set",
+            ArgType::Fn => "a function",
+            ArgType::Atom => "an atom",
+        })
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
 pub enum MalErr {
     ErrString(String),
     ErrMalVal(MalVal),
+    // An error raised by the interpreter as an ex-info map (see
+    // ex_error), whose message already says what went wrong.
+    ErrEx(MalVal),
+    // An error with where it was raised and the mal calls it propagated
+    // out of.
+    ErrTrace(Box<MalErr>, Trace),
+}
+
+impl MalErr {
+    // The value a catch* block binds for this error.
+    pub fn into_value(self) -> MalVal {
+        match self {
+            ErrString(s) => Str(s),
+            ErrMalVal(mv) | ErrEx(mv) => mv,
+            ErrTrace(e, _) => e.into_value(),
+        }
+    }
+
+    // The stack of the mal calls the error propagated out of, as a
+    // vector of frames, innermost first, or nil if not known. A function
+    // that called another in tail position has no frame: tail call
+    // optimization replaced it with the function it called.
+    pub fn stack_value(&self) -> MalVal {
+        match self.stack() {
+            [] => Nil,
+            stack => Vector(
+                Rc::new(stack.iter().map(Frame::to_mal).collect()),
+                Rc::new(Nil),
+            ),
+        }
+    }
+
+    // Attach the location in meta, the metadata of the form being
+    // evaluated, unless the error already has a (more precise) one or
+    // has left the function it was raised in.
+    pub fn at(self, meta: &MalVal) -> MalErr {
+        match (self, SrcLoc::from_meta(meta)) {
+            (e @ ErrTrace(..), _) | (e, None) => e,
+            (e, loc) => ErrTrace(Box::new(e), Trace { loc, stack: vec![] }),
+        }
+    }
+
+    // Record that the error propagated out of a call to the function
+    // named name, from the form with metadata site.
+    pub fn called(self, name: Option<Rc<str>>, site: &MalVal) -> MalErr {
+        let frame = Frame {
+            name,
+            loc: SrcLoc::from_meta(site),
+        };
+        match self {
+            ErrTrace(e, mut trace) => {
+                trace.stack.push(frame);
+                ErrTrace(e, trace)
+            }
+            e => ErrTrace(
+                Box::new(e),
+                Trace {
+                    loc: None,
+                    stack: vec![frame],
+                },
+            ),
+        }
+    }
+
+    // The mal functions the error propagated out of, innermost first.
+    pub fn stack(&self) -> &[Frame] {
+        match self {
+            ErrTrace(_, trace) => &trace.stack,
+            _ => &[],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Trace {
+    pub loc: Option<SrcLoc>,
+    pub stack: Vec<Frame>,
+}
+
+// A call to a mal function, and where it was called from if known.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub name: Option<Rc<str>>,
+    pub loc: Option<SrcLoc>,
+}
+
+impl Frame {
+    // {:fn "name" :file "f.mal" :line 1 :column 2}, leaving out what
+    // isn't known.
+    fn to_mal(&self) -> MalVal {
+        let mut hm = match self.loc {
+            Some(ref loc) => match loc.meta() {
+                Hash(hm, _) => (*hm).clone(),
+                _ => MalMap::default(),
+            },
+            None => MalMap::default(),
+        };
+        if let Some(ref name) = self.name {
+            hm.insert(keyword("fn"), Str(name.to_string()));
+        }
+        Hash(Rc::new(hm), Rc::new(Nil))
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name {
+            Some(ref name) => write!(f, "at {}", name)?,
+            None => write!(f, "at anonymous function")?,
+        }
+        match self.loc {
+            Some(ref loc) => write!(f, " ({})", loc),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SrcLoc {
+    pub file: Rc<str>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SrcLoc {
+    pub fn meta(&self) -> MalVal {
+        let mut hm = MalMap::default();
+        hm.insert(keyword("file"), Str(self.file.to_string()));
+        hm.insert(keyword("line"), Int(self.line as i64));
+        hm.insert(keyword("column"), Int(self.column as i64));
+        Hash(Rc::new(hm), Rc::new(Nil))
+    }
+
+    pub fn from_meta(meta: &MalVal) -> Option<SrcLoc> {
+        let hm = match meta {
+            Hash(hm, _) => hm,
+            _ => return None,
+        };
+        match (
+            hm.get(&keyword("file")),
+            hm.get(&keyword("line")),
+            hm.get(&keyword("column")),
+        ) {
+            (Some(Str(file)), Some(Int(line)), Some(Int(column))) => Some(SrcLoc {
+                file: file.as_str().into(),
+                line: *line as usize,
+                column: *column as usize,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SrcLoc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
 }
 
 pub type MalArgs = Vec<MalVal>;
 pub type MalRet = Result<MalVal, MalErr>;
 
+// Lists, vectors, hash-maps and sets are persistent: an RRB vector and a HAMT
+// that share structure between versions, so conj, assoc, rest and
+// friends cost O(log n) rather than a full copy.
+pub type MalSeq = im_rc::Vector<MalVal>;
+pub type MalMap = im_rc::HashMap<MalVal, MalVal, FnvBuildHasher>;
+pub type MalSet = im_rc::HashSet<MalVal, FnvBuildHasher>;
+
 // type utility macros
 
+#[macro_export]
 macro_rules! list {
   ($seq:expr) => {{
-    List(Rc::new($seq),Rc::new(Nil))
+    $crate::types::MalVal::List(
+      ::std::rc::Rc::new($crate::types::MalSeq::from($seq)),
+      ::std::rc::Rc::new($crate::types::MalVal::Nil),
+    )
   }};
   [$($args:expr),*] => {{
-    let v: Vec<MalVal> = vec![$($args),*];
-    List(Rc::new(v),Rc::new(Nil))
+    let v: Vec<$crate::types::MalVal> = vec![$($args),*];
+    list!(v)
   }}
 }
 
+#[macro_export]
 macro_rules! vector {
   ($seq:expr) => {{
-    Vector(Rc::new($seq),Rc::new(Nil))
+    $crate::types::MalVal::Vector(
+      ::std::rc::Rc::new($crate::types::MalSeq::from($seq)),
+      ::std::rc::Rc::new($crate::types::MalVal::Nil),
+    )
   }};
   [$($args:expr),*] => {{
-    let v: Vec<MalVal> = vec![$($args),*];
-    Vector(Rc::new(v),Rc::new(Nil))
+    let v: Vec<$crate::types::MalVal> = vec![$($args),*];
+    vector!(v)
   }}
 }
 
@@ -67,67 +415,303 @@ pub fn error(s: &str) -> MalRet {
     Err(ErrString(s.to_string()))
 }
 
+// An ex-info is a map of its data, with its message and cause (if it
+// has one) under the keys :ex/message and :ex/cause. The ex namespace is
+// reserved for these, so the data may have any other keys.
+pub fn ex_key(name: &str) -> MalVal {
+    keyword(&format!("ex/{}", name))
+}
+
+// An error thrown as an ex-info map: the given data, with the type of
+// error as :type, e.g.
+// {:type :arity :fn "nth" :got 0 :ex/message "wrong number of args ..."}
+pub fn ex_error(t: &str, msg: &str, data: Vec<(&str, MalVal)>) -> MalErr {
+    let mut hm = MalMap::default();
+    hm.insert(keyword("type"), keyword(t));
+    for (k, v) in data {
+        hm.insert(keyword(k), v);
+    }
+    hm.insert(ex_key("message"), Str(msg.to_string()));
+    ErrEx(Hash(Rc::new(hm), Rc::new(Nil)))
+}
+
+// The error for a value a function or form can't take, e.g. (get 1 :a)
+// or (1 2), with the value as :value.
+pub fn type_error(msg: &str, v: &MalVal) -> MalErr {
+    ex_error("wrong-type", msg, vec![("value", v.clone())])
+}
+
+// The error for a malformed special form, e.g. (fn* [&] 1).
+pub fn syntax_error(msg: &str) -> MalErr {
+    ex_error("syntax", msg, vec![])
+}
+
 pub fn format_error(e: MalErr) -> String {
-    match e {
-        ErrString(s) => s,
-        ErrMalVal(mv) => mv.pr_str(true),
+    e.to_string()
+}
+
+impl fmt::Display for MalErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrString(s) => write!(f, "{}", s),
+            ErrEx(mv) => write!(f, "{}", mv.ex_message().unwrap_or_default()),
+            // an ex-info shows its message, then any data
+            ErrMalVal(mv) => match (mv.ex_message(), mv.ex_data()) {
+                (Some(msg), Some(Hash(hm, _))) if hm.is_empty() => write!(f, "{}", msg),
+                (Some(msg), Some(data)) => write!(f, "{} {}", msg, data.pr_str(true)),
+                _ => write!(f, "{}", mv.pr_str(true)),
+            },
+            ErrTrace(e, Trace { loc: Some(loc), .. }) => write!(f, "{}: {}", loc, e),
+            ErrTrace(e, _) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl fmt::Debug for MalErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for MalErr {}
+
+// Numbers are kept in the narrowest type that holds them exactly, so
+// that equal values always compare equal.
+pub fn from_big(b: num_bigint::BigInt) -> MalVal {
+    match b.to_i64() {
+        Some(i) => Int(i),
+        None => BigInt(b),
+    }
+}
+
+pub fn from_ratio(r: BigRational) -> MalVal {
+    if r.denom().is_one() {
+        from_big(r.to_integer())
+    } else {
+        Ratio(r)
+    }
+}
+
+// Keywords are interned so that comparing two of them is a pointer
+// comparison. The table only grows, like the set of symbols a program
+// mentions.
+pub fn keyword(name: &str) -> MalVal {
+    thread_local! {
+        static KEYWORDS: RefCell<FnvHashMap<String, Rc<str>>> =
+            RefCell::new(FnvHashMap::default());
     }
+    KEYWORDS.with(|kws| {
+        let mut kws = kws.borrow_mut();
+        if let Some(k) = kws.get(name) {
+            return Keyword(k.clone());
+        }
+        let k: Rc<str> = Rc::from(name);
+        kws.insert(name.to_string(), k.clone());
+        Keyword(k)
+    })
+}
+
+// A fresh symbol for a macro to bind: prefix followed by the next value
+// of a global counter and __auto__, a suffix user symbols are unlikely
+// to have, e.g. x__12__auto__ for x#.
+pub fn gensym(prefix: &str) -> MalVal {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    Sym(format!(
+        "{}{}__auto__",
+        prefix,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
 }
 
 pub fn atom(mv: &MalVal) -> MalVal {
     Atom(Rc::new(RefCell::new(mv.clone())))
 }
 
+pub fn lazy_seq(f: MalVal, args: MalArgs) -> MalVal {
+    LazySeq(Rc::new(RefCell::new(Lazy::Pending(f, args))))
+}
+
+pub fn lazy_cons(first: MalVal, rest: MalVal) -> MalVal {
+    LazySeq(Rc::new(RefCell::new(Lazy::Realized(Some((first, rest))))))
+}
+
+// Realize a lazy seq cell. The function of a pending cell may return
+// another pending lazy seq, as a recursive (lazy-seq ...) body does
+// when it skips elements; that chain is followed in a loop rather than
+// by recursion and every cell on it is filled in with the result.
+fn realize(cell: &Rc<RefCell<Lazy>>) -> Result<Option<(MalVal, MalVal)>, MalErr> {
+    let mut cells = vec![cell.clone()];
+    let step = loop {
+        let (f, args) = match &*cells[cells.len() - 1].borrow() {
+            Lazy::Realized(step) => break step.clone(),
+            Lazy::Pending(f, args) => (f.clone(), args.clone()),
+        };
+        match f.apply(args)? {
+            LazySeq(next) => cells.push(next),
+            v @ (Nil | List(_, _) | Vector(_, _) | Set(_, _)) => break v.uncons()?,
+            v => {
+                let msg = format!("lazy-seq: {} is not a seq", v.pr_str(true));
+                return Err(type_error(&msg, &v));
+            }
+        }
+    };
+    for c in cells {
+        *c.borrow_mut() = Lazy::Realized(step.clone());
+    }
+    Ok(step)
+}
+
+// Dropping a long realized chain recursively could overflow the stack,
+// so unlink the cells that are not shared with anything else one by one.
+impl Drop for Lazy {
+    fn drop(&mut self) {
+        let mut next = match self {
+            Lazy::Realized(Some((_, rest))) => std::mem::replace(rest, Nil),
+            _ => return,
+        };
+        while let LazySeq(cell) = next {
+            next = match Rc::try_unwrap(cell) {
+                Ok(cell) => match &mut *cell.borrow_mut() {
+                    Lazy::Realized(Some((_, rest))) => std::mem::replace(rest, Nil),
+                    _ => Nil,
+                },
+                Err(_) => Nil,
+            }
+        }
+    }
+}
+
+// Iterates over the elements of a seq, realizing lazy cells on the way.
+// An error ends the iteration after it has been returned.
+pub struct SeqIter(MalVal);
+
+impl Iterator for SeqIter {
+    type Item = MalRet;
+
+    fn next(&mut self) -> Option<MalRet> {
+        match self.0.uncons() {
+            Ok(Some((x, rest))) => {
+                self.0 = rest;
+                Some(Ok(x))
+            }
+            Ok(None) => None,
+            Err(e) => {
+                self.0 = Nil;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
 impl MalVal {
     pub fn keyword(&self) -> MalRet {
         match self {
-            Str(s) if s.starts_with('\u{29e}') => Ok(Str(s.to_string())),
-            Str(s) => Ok(Str(format!("\u{29e}{}", s))),
-            _ => error("invalid type for keyword"),
+            Keyword(_) => Ok(self.clone()),
+            Str(s) => Ok(keyword(s)),
+            _ => Err(type_error("invalid type for keyword", self)),
         }
     }
 
     pub fn empty_q(&self) -> MalRet {
         match self {
-            List(l, _) | Vector(l, _) => Ok(Bool(l.len() == 0)),
+            List(l, _) | Vector(l, _) => Ok(Bool(l.is_empty())),
+            Set(s, _) => Ok(Bool(s.is_empty())),
             Nil => Ok(Bool(true)),
-            _ => error("invalid type for empty?"),
+            LazySeq(_) => Ok(Bool(self.uncons()?.is_none())),
+            _ => Err(type_error("invalid type for empty?", self)),
         }
     }
 
     pub fn count(&self) -> MalRet {
         match self {
             List(l, _) | Vector(l, _) => Ok(Int(l.len() as i64)),
+            Set(s, _) => Ok(Int(s.len() as i64)),
             Nil => Ok(Int(0)),
-            _ => error("invalid type for count"),
+            LazySeq(_) => {
+                let mut n = 0;
+                for x in self.seq_iter() {
+                    x?;
+                    n += 1;
+                }
+                Ok(Int(n))
+            }
+            _ => Err(type_error("invalid type for count", self)),
+        }
+    }
+
+    // Split a seq into its first element and the rest, realizing at
+    // most one cell of a lazy seq. Returns None for an empty seq.
+    pub fn uncons(&self) -> Result<Option<(MalVal, MalVal)>, MalErr> {
+        match self {
+            List(l, _) | Vector(l, _) if l.is_empty() => Ok(None),
+            List(l, _) | Vector(l, _) => Ok(Some((l[0].clone(), list!(l.skip(1))))),
+            Set(s, _) => list!(s.iter().cloned().collect::<MalSeq>()).uncons(),
+            Nil => Ok(None),
+            LazySeq(cell) => realize(cell),
+            _ => Err(type_error(
+                &format!("{} is not a seq", self.pr_str(true)),
+                self,
+            )),
         }
     }
 
+    pub fn seq_iter(&self) -> SeqIter {
+        SeqIter(self.clone())
+    }
+
+    // The elements of a seq, realizing the whole of a lazy one.
+    pub fn realize_all(&self) -> Result<MalSeq, MalErr> {
+        self.seq_iter().collect()
+    }
+
     pub fn apply(&self, args: MalArgs) -> MalRet {
         match self {
-            Func(f, _) => f(args),
+            Func(b, _) => {
+                b.check_args(&args)?;
+                (b.f)(args)
+            }
             MalFunc {
                 eval,
                 ref ast,
                 env,
                 ref params,
+                ref name,
                 ..
             } => {
-                let fn_env = &env_bind(Some(env.clone()), params, args)?;
-                eval(ast, fn_env)
+                let (params, body) = fn_clause(name.as_deref(), params, ast, args.len())?;
+                let fn_env = &env_bind(Some(env.clone()), params, args, name.as_deref())?;
+                eval(body, fn_env).map_err(|e| e.called(name.clone(), &Nil))
             }
-            _ => error("attempt to call non-function"),
+            _ => Err(type_error("attempt to call non-function", self)),
+        }
+    }
+
+    // The message of an ex-info (see ex_key).
+    pub fn ex_message(&self) -> Option<String> {
+        match self {
+            Hash(hm, _) => match hm.get(&ex_key("message")) {
+                Some(Str(msg)) => Some(msg.clone()),
+                _ => None,
+            },
+            _ => None,
         }
     }
 
-    pub fn keyword_q(&self) -> bool {
-        matches!(self, Str(s) if s.starts_with('\u{29e}'))
+    // The data of an ex-info: the map without its message and cause.
+    pub fn ex_data(&self) -> Option<MalVal> {
+        match self {
+            Hash(hm, _) if self.ex_message().is_some() => {
+                let hm = hm.without(&ex_key("message")).without(&ex_key("cause"));
+                Some(Hash(Rc::new(hm), Rc::new(Nil)))
+            }
+            _ => None,
+        }
     }
 
     pub fn deref(&self) -> MalRet {
         match self {
             Atom(a) => Ok(a.borrow().clone()),
-            _ => error("attempt to deref a non-Atom"),
+            _ => Err(type_error("attempt to deref a non-Atom", self)),
         }
     }
 
@@ -137,7 +721,7 @@ impl MalVal {
                 *a.borrow_mut() = new.clone();
                 Ok(new.clone())
             }
-            _ => error("attempt to reset! a non-Atom"),
+            _ => Err(type_error("attempt to reset! a non-Atom", self)),
         }
     }
 
@@ -150,16 +734,16 @@ impl MalVal {
                 *a.borrow_mut() = f.apply(fargs)?;
                 Ok(a.borrow().clone())
             }
-            _ => error("attempt to swap! a non-Atom"),
+            _ => Err(type_error("attempt to swap! a non-Atom", self)),
         }
     }
 
     pub fn get_meta(&self) -> MalRet {
         match self {
-            List(_, meta) | Vector(_, meta) | Hash(_, meta) => Ok((**meta).clone()),
+            List(_, meta) | Vector(_, meta) | Hash(_, meta) | Set(_, meta) => Ok((**meta).clone()),
             Func(_, meta) => Ok((**meta).clone()),
             MalFunc { meta, .. } => Ok((**meta).clone()),
-            _ => error("meta not supported by type"),
+            _ => Err(type_error("meta not supported by type", self)),
         }
     }
 
@@ -168,11 +752,24 @@ impl MalVal {
             List(_, ref mut meta)
             | Vector(_, ref mut meta)
             | Hash(_, ref mut meta)
-            | Func(_, ref mut meta)
-            | MalFunc { ref mut meta, .. } => {
+            | Set(_, ref mut meta)
+            | Func(_, ref mut meta) => {
+                *meta = Rc::new(new_meta.clone());
+            }
+            MalFunc {
+                ref mut meta,
+                ref mut name,
+                ..
+            } => {
                 *meta = Rc::new(new_meta.clone());
+                if let Hash(hm, _) = new_meta {
+                    match hm.get(&keyword("name")) {
+                        Some(Str(s)) | Some(Sym(s)) => *name = Some(s.as_str().into()),
+                        _ => (),
+                    }
+                }
             }
-            _ => return error("with-meta not supported by type"),
+            _ => return Err(type_error("with-meta not supported by type", self)),
         };
         Ok(self.clone())
     }
@@ -184,51 +781,372 @@ impl PartialEq for MalVal {
             (Nil, Nil) => true,
             (Bool(ref a), Bool(ref b)) => a == b,
             (Int(ref a), Int(ref b)) => a == b,
+            (BigInt(ref a), BigInt(ref b)) => a == b,
+            (Ratio(ref a), Ratio(ref b)) => a == b,
+            // NaN equals itself here, so that it can be a key or set
+            // element like any other value
+            (Float(ref a), Float(ref b)) => a == b || (a.is_nan() && b.is_nan()),
             (Str(ref a), Str(ref b)) => a == b,
+            (Keyword(ref a), Keyword(ref b)) => Rc::ptr_eq(a, b),
             (Sym(ref a), Sym(ref b)) => a == b,
             (List(ref a, _), List(ref b, _))
             | (Vector(ref a, _), Vector(ref b, _))
             | (List(ref a, _), Vector(ref b, _))
             | (Vector(ref a, _), List(ref b, _)) => a == b,
             (Hash(ref a, _), Hash(ref b, _)) => a == b,
-            (MalFunc { .. }, MalFunc { .. }) => false,
+            (Set(ref a, _), Set(ref b, _)) => a == b,
+            (Atom(ref a), Atom(ref b)) => Rc::ptr_eq(a, b),
+            (LazySeq(_), List(_, _) | Vector(_, _) | LazySeq(_))
+            | (List(_, _) | Vector(_, _), LazySeq(_)) => {
+                // an error while realizing either side ends the comparison
+                self.seq_iter()
+                    .map(|x| x.ok())
+                    .eq(other.seq_iter().map(|x| x.ok()))
+            }
+            // functions are equal when they are the same function,
+            // whatever their metadata
+            (Func(ref a, _), Func(ref b, _)) => Rc::ptr_eq(a, b),
+            (
+                MalFunc {
+                    ast: ref a,
+                    env: ref ae,
+                    params: ref ap,
+                    ..
+                },
+                MalFunc {
+                    ast: ref b,
+                    env: ref be,
+                    params: ref bp,
+                    ..
+                },
+            ) => Rc::ptr_eq(a, b) && Rc::ptr_eq(ae, be) && Rc::ptr_eq(ap, bp),
             _ => false,
         }
     }
 }
 
-pub fn func(f: fn(MalArgs) -> MalRet) -> MalVal {
-    Func(f, Rc::new(Nil))
+impl Eq for MalVal {}
+
+// Hashing agrees with `=`: lists and vectors with the same elements
+// hash alike, maps and sets hash independently of their iteration
+// order and atoms and functions hash by identity.
+impl std::hash::Hash for MalVal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            List(l, _) | Vector(l, _) => l.hash(state),
+            LazySeq(_) => {
+                for x in self.seq_iter().map_while(Result::ok) {
+                    x.hash(state)
+                }
+            }
+            _ => {
+                std::mem::discriminant(self).hash(state);
+                match self {
+                    Bool(b) => b.hash(state),
+                    Int(i) => i.hash(state),
+                    BigInt(b) => b.hash(state),
+                    Ratio(r) => r.hash(state),
+                    // 0.0 and -0.0 are equal
+                    Float(f) if *f == 0.0 => 0u64.hash(state),
+                    Float(f) if f.is_nan() => f64::NAN.to_bits().hash(state),
+                    Float(f) => f.to_bits().hash(state),
+                    Str(s) | Sym(s) => s.hash(state),
+                    Keyword(k) => k.hash(state),
+                    Hash(hm, _) => {
+                        let mut sum: u64 = 0;
+                        for (k, v) in hm.iter() {
+                            let mut h = FnvHasher::default();
+                            k.hash(&mut h);
+                            v.hash(&mut h);
+                            sum = sum.wrapping_add(h.finish());
+                        }
+                        sum.hash(state)
+                    }
+                    Set(s, _) => {
+                        let mut sum: u64 = 0;
+                        for v in s.iter() {
+                            let mut h = FnvHasher::default();
+                            v.hash(&mut h);
+                            sum = sum.wrapping_add(h.finish());
+                        }
+                        sum.hash(state)
+                    }
+                    Atom(a) => Rc::as_ptr(a).hash(state),
+                    Func(f, _) => Rc::as_ptr(f).hash(state),
+                    MalFunc { ast, .. } => Rc::as_ptr(ast).hash(state),
+                    _ => (),
+                }
+            }
+        }
+    }
+}
+
+// The number of fixed parameters in a parameter list, and whether it
+// takes more through &.
+pub fn param_count(params: &MalVal) -> (usize, bool) {
+    match params {
+        List(ps, _) | Vector(ps, _) => match ps.iter().position(|p| *p == Sym("&".to_string())) {
+            Some(n) => (n, true),
+            None => (ps.len(), false),
+        },
+        _ => (0, false),
+    }
+}
+
+// Pick the parameters and body a function is called with. A multi-arity
+// function has nil params and a list of (params body) clauses as its
+// ast; the clause taking exactly nargs wins over a variadic one.
+pub fn fn_clause<'a>(
+    name: Option<&str>,
+    params: &'a MalVal,
+    ast: &'a MalVal,
+    nargs: usize,
+) -> Result<(&'a MalVal, &'a MalVal), MalErr> {
+    let clauses = match (params, ast) {
+        (Nil, List(clauses, _)) => clauses,
+        _ => return Ok((params, ast)),
+    };
+    let mut variadic = None;
+    let mut arities = vec![];
+    for c in clauses.iter() {
+        if let List(c, _) = c {
+            match param_count(&c[0]) {
+                (n, false) if n == nargs => return Ok((&c[0], &c[1])),
+                (n, true) if n <= nargs => variadic = Some((&c[0], &c[1])),
+                (n, false) => arities.push(n.to_string()),
+                (n, true) => arities.push(format!("at least {}", n)),
+            }
+        }
+    }
+    variadic.ok_or_else(|| {
+        let expected = match arities.split_last() {
+            Some((last, [])) => last.clone(),
+            Some((last, init)) => format!("{} or {}", init.join(", "), last),
+            None => "0".to_string(),
+        };
+        arg_count_error(name, &expected, nargs)
+    })
+}
+
+// How errors refer to a mal function, by its name if it has one.
+pub fn describe_fn(name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!("function {}", name),
+        None => "anonymous function".to_string(),
+    }
+}
+
+// The error for calling a mal function with the wrong number of
+// arguments; expected describes what it takes, e.g. "2" or "at least 1".
+pub fn arg_count_error(name: Option<&str>, expected: &str, got: usize) -> MalErr {
+    let f = describe_fn(name);
+    let args = match expected {
+        "1" | "at least 1" => "arg",
+        _ => "args",
+    };
+    let msg = format!("{} expected {} {}, got {}", f, expected, args, got);
+    let name = name.map_or(Nil, |name| Str(name.to_string()));
+    ex_error("arity", &msg, vec![("fn", name), ("got", Int(got as i64))])
 }
 
-pub fn _assoc(mut hm: FnvHashMap<String, MalVal>, kvs: MalArgs) -> MalRet {
-    if kvs.len() % 2 != 0 {
-        return error("odd number of elements");
+pub fn func(f: impl Fn(MalArgs) -> MalRet + 'static) -> MalVal {
+    builtin("", None, f)
+}
+
+pub fn builtin(
+    name: &str,
+    arity: Option<Arity>,
+    f: impl Fn(MalArgs) -> MalRet + 'static,
+) -> MalVal {
+    Func(
+        Rc::new(Builtin {
+            name: name.to_string(),
+            arity,
+            types: vec![],
+            f: Rc::new(f),
+        }),
+        Rc::new(Nil),
+    )
+}
+
+pub fn _assoc(mut hm: MalMap, kvs: MalArgs) -> MalRet {
+    if !kvs.len().is_multiple_of(2) {
+        // the last key has no value
+        return Err(type_error("odd number of elements", &kvs[kvs.len() - 1]));
     }
     for (k, v) in kvs.iter().tuples() {
         match k {
-            Str(s) => {
-                hm.insert(s.to_string(), v.clone());
+            Atom(_) => return Err(type_error("atom cannot be a hash-map key", k)),
+            _ => {
+                hm.insert(k.clone(), v.clone());
             }
-            _ => return error("key is not string"),
         }
     }
     Ok(Hash(Rc::new(hm), Rc::new(Nil)))
 }
 
-pub fn _dissoc(mut hm: FnvHashMap<String, MalVal>, ks: MalArgs) -> MalRet {
+pub fn _dissoc(mut hm: MalMap, ks: MalArgs) -> MalRet {
     for k in ks {
-        match k {
-            Str(ref s) => {
-                let _ = hm.remove(s);
-            }
-            _ => return error("key is not string"),
-        }
+        let _ = hm.remove(&k);
     }
     Ok(Hash(Rc::new(hm), Rc::new(Nil)))
 }
 
 pub fn hash_map(kvs: MalArgs) -> MalRet {
-    let hm: FnvHashMap<String, MalVal> = FnvHashMap::default();
-    _assoc(hm, kvs)
+    _assoc(MalMap::default(), kvs)
+}
+
+pub fn _conj_set(mut set: MalSet, vs: MalArgs) -> MalRet {
+    for v in vs {
+        match v {
+            Atom(_) => return Err(type_error("atom cannot be a set element", &v)),
+            _ => {
+                set.insert(v);
+            }
+        }
+    }
+    Ok(Set(Rc::new(set), Rc::new(Nil)))
+}
+
+pub fn hash_set(vs: MalArgs) -> MalRet {
+    _conj_set(MalSet::default(), vs)
+}
+
+// Conversions for host code: plain Rust values convert into mal values,
+// and TryFrom converts back, failing when the mal value has the wrong
+// type. Rust Vecs become vectors; any seq converts back to a Vec, and
+// keywords convert to their name as a String so that a map with keyword
+// keys converts to a HashMap<String, _>.
+
+impl From<bool> for MalVal {
+    fn from(b: bool) -> MalVal {
+        Bool(b)
+    }
+}
+
+impl From<i64> for MalVal {
+    fn from(i: i64) -> MalVal {
+        Int(i)
+    }
+}
+
+impl From<f64> for MalVal {
+    fn from(f: f64) -> MalVal {
+        Float(f)
+    }
+}
+
+impl From<&str> for MalVal {
+    fn from(s: &str) -> MalVal {
+        Str(s.to_string())
+    }
+}
+
+impl From<String> for MalVal {
+    fn from(s: String) -> MalVal {
+        Str(s)
+    }
+}
+
+impl<T: Into<MalVal>> From<Option<T>> for MalVal {
+    fn from(o: Option<T>) -> MalVal {
+        o.map_or(Nil, Into::into)
+    }
+}
+
+impl<T: Into<MalVal>> From<Vec<T>> for MalVal {
+    fn from(v: Vec<T>) -> MalVal {
+        vector!(v.into_iter().map(Into::into).collect::<MalSeq>())
+    }
+}
+
+impl<K: Into<MalVal>, V: Into<MalVal>, S> From<HashMap<K, V, S>> for MalVal {
+    fn from(hm: HashMap<K, V, S>) -> MalVal {
+        let hm: MalMap = hm.into_iter().map(|(k, v)| (k.into(), v.into())).collect();
+        Hash(Rc::new(hm), Rc::new(Nil))
+    }
+}
+
+fn conversion_error(expected: &str, v: &MalVal) -> MalErr {
+    ErrString(format!("expected {}, got {}", expected, v.pr_str(true)))
+}
+
+impl TryFrom<MalVal> for bool {
+    type Error = MalErr;
+
+    fn try_from(v: MalVal) -> Result<bool, MalErr> {
+        match v {
+            Bool(b) => Ok(b),
+            _ => Err(conversion_error("a boolean", &v)),
+        }
+    }
+}
+
+impl TryFrom<MalVal> for i64 {
+    type Error = MalErr;
+
+    fn try_from(v: MalVal) -> Result<i64, MalErr> {
+        match v {
+            Int(i) => Ok(i),
+            _ => Err(conversion_error("an integer", &v)),
+        }
+    }
+}
+
+impl TryFrom<MalVal> for f64 {
+    type Error = MalErr;
+
+    fn try_from(v: MalVal) -> Result<f64, MalErr> {
+        match v {
+            Int(i) => Ok(i as f64),
+            BigInt(ref b) => b.to_f64().ok_or_else(|| conversion_error("a number", &v)),
+            Ratio(ref r) => r.to_f64().ok_or_else(|| conversion_error("a number", &v)),
+            Float(f) => Ok(f),
+            _ => Err(conversion_error("a number", &v)),
+        }
+    }
+}
+
+impl TryFrom<MalVal> for String {
+    type Error = MalErr;
+
+    fn try_from(v: MalVal) -> Result<String, MalErr> {
+        match v {
+            Str(s) => Ok(s),
+            Keyword(k) => Ok(k.to_string()),
+            _ => Err(conversion_error("a string", &v)),
+        }
+    }
+}
+
+impl<T: TryFrom<MalVal, Error = MalErr>> TryFrom<MalVal> for Vec<T> {
+    type Error = MalErr;
+
+    fn try_from(v: MalVal) -> Result<Vec<T>, MalErr> {
+        match v {
+            Nil | List(_, _) | Vector(_, _) | Set(_, _) | LazySeq(_) => {
+                v.realize_all()?.into_iter().map(T::try_from).collect()
+            }
+            _ => Err(conversion_error("a seq", &v)),
+        }
+    }
+}
+
+impl<K, V, S> TryFrom<MalVal> for HashMap<K, V, S>
+where
+    K: TryFrom<MalVal, Error = MalErr> + Eq + std::hash::Hash,
+    V: TryFrom<MalVal, Error = MalErr>,
+    S: BuildHasher + Default,
+{
+    type Error = MalErr;
+
+    fn try_from(v: MalVal) -> Result<HashMap<K, V, S>, MalErr> {
+        match v {
+            Hash(hm, _) => hm
+                .iter()
+                .map(|(k, v)| Ok((K::try_from(k.clone())?, V::try_from(v.clone())?)))
+                .collect(),
+            _ => Err(conversion_error("a hash-map", &v)),
+        }
+    }
 }